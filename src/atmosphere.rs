@@ -0,0 +1,49 @@
+
+use nalgebra_glm::dot;
+use crate::Uniforms;
+use crate::fragment::Fragment;
+use crate::color::Color;
+
+// Transparent shell drawn slightly above a planet surface, glowing along the rim
+#[derive(Debug, Clone, Copy)]
+pub struct Atmosphere {
+    pub color: Color,
+    // Shell height as a fraction of the planet radius
+    pub thickness: f32,
+    // Opacity where the glow is strongest, 0..1
+    pub density: f32,
+    // Fresnel exponent, higher values squeeze the glow towards the silhouette
+    pub falloff: f32,
+}
+
+impl Atmosphere {
+    pub fn new(color: Color, thickness: f32, density: f32) -> Self {
+        Atmosphere {
+            color,
+            thickness,
+            density,
+            falloff: 2.0,
+        }
+    }
+
+    pub fn with_falloff(mut self, falloff: f32) -> Self {
+        self.falloff = falloff;
+        self
+    }
+}
+
+// Color and opacity of a shell fragment. Grazing view angles look through more air, so the
+// opacity follows a Fresnel term, fading out again right at the outer edge of the shell.
+pub fn atmosphere_shader(fragment: &Fragment, uniforms: &Uniforms, atmosphere: &Atmosphere) -> (Color, f32) {
+    let view_dir = (-fragment.view_position).normalize();
+    let facing = dot(&fragment.view_normal, &view_dir).clamp(0.0, 1.0);
+    let fresnel = (1.0 - facing).powf(atmosphere.falloff);
+    let edge = (facing / 0.1).min(1.0);
+
+    // Scattering carries some light a bit past the terminator into the night side
+    let light_dir = uniforms.light.direction_to(&fragment.world_position);
+    let lit = ((dot(&fragment.normal, &light_dir) + 0.3) / 1.3).clamp(0.0, 1.0) * uniforms.light.intensity;
+
+    let alpha = (fresnel * edge * atmosphere.density * lit).clamp(0.0, 1.0);
+    (atmosphere.color, alpha)
+}
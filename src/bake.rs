@@ -0,0 +1,131 @@
+
+use nalgebra_glm::{Vec2, Vec3, Mat4};
+use crate::Uniforms;
+use crate::vertex::Vertex;
+use crate::fragment::Fragment;
+use crate::color::Color;
+use crate::light::Light;
+use crate::texture::Texture;
+use crate::triangle::triangle;
+use crate::shaders::{fragment_shader, Shader};
+use crate::noise::{lat_long, lat_long_to_direction};
+
+// How a baked texture wraps around a mesh
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureMapping {
+    // The mesh texture coordinates
+    Uv,
+    // Longitude and latitude of the object space position, for spheres without usable UVs
+    Equirectangular,
+}
+
+impl TextureMapping {
+    pub fn coords(&self, fragment: &Fragment) -> Vec2 {
+        match self {
+            TextureMapping::Uv => fragment.tex_coords,
+            TextureMapping::Equirectangular => lat_long(&fragment.vertex_position),
+        }
+    }
+}
+
+// Shades one texel. Every texel is lit by a light straight above it, so the texture keeps the
+// surface colors and bump detail while the real lighting and shadows are added at runtime.
+fn shade_texel(fragment: &Fragment, shader: &Shader, time: f32) -> Color {
+    let uniforms = Uniforms {
+        model_matrix: Mat4::identity(),
+        view_matrix: Mat4::identity(),
+        projection_matrix: Mat4::identity(),
+        viewport_matrix: Mat4::identity(),
+        time,
+        light: Light::directional(-fragment.normal, 1.0),
+        normal_map: None,
+        displacement: None
+    };
+    fragment_shader(fragment, &uniforms, shader)
+}
+
+// Bakes the shader over a sphere of the given radius, u is the longitude and v the latitude
+pub fn bake_equirectangular(shader: &Shader, radius: f32, width: usize, height: usize, time: f32) -> Texture {
+    let mut texture = Texture::new(width, height);
+
+    for y in 0..height {
+        for x in 0..width {
+            let uv = Vec2::new((x as f32 + 0.5) / width as f32, (y as f32 + 0.5) / height as f32);
+            let normal = lat_long_to_direction(&uv);
+            let position = normal * radius;
+            // East along the parallel, stays defined at the poles
+            let longitude = (uv.x - 0.5) * 2.0 * std::f32::consts::PI;
+            let tangent = Vec3::new(-longitude.sin(), 0.0, longitude.cos());
+
+            let fragment = Fragment {
+                world_position: position,
+                tex_coords: uv,
+                tangent,
+                bitangent: normal.cross(&tangent),
+                ..Fragment::new(x as f32, y as f32, Color::black(), 0.0, normal, 1.0, position)
+            };
+            texture.set_pixel(x, y, shade_texel(&fragment, shader, time));
+        }
+    }
+
+    texture
+}
+
+// Bakes the shader in the mesh UV space by rasterizing every triangle at its texture coordinates
+pub fn bake_uv(shader: &Shader, vertex_array: &[Vertex], width: usize, height: usize, time: f32) -> Texture {
+    let mut texture = Texture::new(width, height);
+    let mut covered = vec![false; width * height];
+    let light = Light::directional(Vec3::new(0.0, 0.0, -1.0), 1.0);
+
+    for tri in vertex_array.chunks_exact(3) {
+        let unwrapped: Vec<Vertex> = tri.iter().map(|vertex| {
+            let mut unwrapped = vertex.clone();
+            unwrapped.transformed_position = Vec3::new(vertex.tex_coords.x * width as f32, vertex.tex_coords.y * height as f32, 0.0);
+            unwrapped.world_tangent = vertex.tangent;
+            unwrapped.world_bitangent = vertex.bitangent;
+            unwrapped
+        }).collect();
+
+        for mut fragment in triangle(&unwrapped[0], &unwrapped[1], &unwrapped[2], &light) {
+            let x = fragment.position.x as usize;
+            let y = fragment.position.y as usize;
+            if fragment.position.x < 0.0 || fragment.position.y < 0.0 || x >= width || y >= height {
+                continue;
+            }
+            fragment.intensity = 1.0;
+            texture.set_pixel(x, y, shade_texel(&fragment, shader, time));
+            covered[y * width + x] = true;
+        }
+    }
+
+    dilate(&mut texture, &mut covered, 4);
+    texture
+}
+
+// Grows the baked islands into the empty texels around them, so bilinear filtering near the
+// UV seams does not blend in the black background
+fn dilate(texture: &mut Texture, covered: &mut [bool], passes: usize) {
+    let (width, height) = (texture.width, texture.height);
+
+    for _ in 0..passes {
+        let mut grown = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                if covered[y * width + x] {
+                    continue;
+                }
+                let neighbor = [(-1, 0), (1, 0), (0, -1), (0, 1)].iter()
+                    .map(|(dx, dy)| (x as i64 + dx, y as i64 + dy))
+                    .filter(|&(nx, ny)| nx >= 0 && ny >= 0 && (nx as usize) < width && (ny as usize) < height)
+                    .find(|&(nx, ny)| covered[ny as usize * width + nx as usize]);
+                if let Some((nx, ny)) = neighbor {
+                    grown.push((x, y, texture.get_pixel(nx as usize, ny as usize)));
+                }
+            }
+        }
+        for (x, y, color) in grown {
+            texture.set_pixel(x, y, color);
+            covered[y * width + x] = true;
+        }
+    }
+}
@@ -1,11 +1,80 @@
 
-use nalgebra_glm::{Vec3, rotate_vec3};
+use nalgebra_glm::{Vec3, Mat3, Mat4, Quat, rotate_vec3, perspective, ortho, quat_angle_axis, quat_slerp, mat3_to_quat, quat_to_mat3};
 use std::f32::consts::PI;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProjectionMode {
+  Perspective,
+  // No foreshortening, handy to look at a shader without distortion
+  Orthographic,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Projection {
+  pub mode: ProjectionMode,
+  // Vertical field of view in radians
+  pub fov: f32,
+  pub near: f32,
+  pub far: f32,
+}
+
+impl Default for Projection {
+  fn default() -> Self {
+    Projection {
+      mode: ProjectionMode::Perspective,
+      fov: 45.0 * PI / 180.0,
+      near: 0.1,
+      far: 1000.0,
+    }
+  }
+}
+
+impl Projection {
+  pub fn perspective_matrix(&self, aspect_ratio: f32) -> Mat4 {
+    perspective(aspect_ratio, self.fov, self.near, self.far)
+  }
+
+  // The orthographic view spans what the perspective one shows at the given distance, so
+  // switching between them keeps the target the same size
+  pub fn matrix(&self, aspect_ratio: f32, distance: f32) -> Mat4 {
+    match self.mode {
+      ProjectionMode::Perspective => self.perspective_matrix(aspect_ratio),
+      ProjectionMode::Orthographic => {
+        let half_height = distance * (self.fov * 0.5).tan();
+        let half_width = half_height * aspect_ratio;
+        ortho(-half_width, half_width, -half_height, half_height, self.near, self.far)
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraMode {
+  // Circles around the center
+  Orbit,
+  // First person, turns around the eye and moves it freely
+  Fly,
+}
+
+// Point the camera keeps as its center, and the distance it is still moving to, which is
+// dropped once reached so zooming works while focused
+struct Focus {
+  center: Vec3,
+  distance: Option<f32>,
+}
+
 pub struct Camera {
   pub eye: Vec3,
   pub center: Vec3,
   pub up: Vec3,
+  pub projection: Projection,
+  pub mode: CameraMode,
+  // Rotates around the camera's own axes with quaternions instead of yaw and pitch, so the view
+  // can go over the poles and roll
+  pub free_rotation: bool,
+  // Orientation the camera is turning to, its start and the progress from 0 to 1
+  transition: Option<(Quat, Quat, f32)>,
+  focus: Option<Focus>,
   pub has_changed: bool
 }
 
@@ -15,10 +84,56 @@ impl Camera {
       eye,
       center,
       up,
+      projection: Projection::default(),
+      mode: CameraMode::Orbit,
+      free_rotation: false,
+      transition: None,
+      focus: None,
       has_changed: true,
     }
   }
 
+  pub fn projection_matrix(&self, aspect_ratio: f32) -> Mat4 {
+    self.projection.matrix(aspect_ratio, self.distance())
+  }
+
+  // Field of view change in radians, kept between 10 and 120 degrees
+  pub fn change_fov(&mut self, delta: f32) {
+    self.projection.fov = (self.projection.fov + delta).clamp(10.0_f32.to_radians(), 120.0_f32.to_radians());
+    self.has_changed = true;
+  }
+
+  // Clip plane changes by a factor, the near plane stays in front of the eye and both stay
+  // well apart so depth keeps some precision
+  pub fn scale_near(&mut self, factor: f32) {
+    self.projection.near = (self.projection.near * factor).clamp(0.001, self.projection.far * 0.5);
+    self.has_changed = true;
+  }
+
+  pub fn scale_far(&mut self, factor: f32) {
+    self.projection.far = (self.projection.far * factor).clamp(self.projection.near * 2.0, 100000.0);
+    self.has_changed = true;
+  }
+
+  pub fn toggle_orthographic(&mut self) {
+    self.projection.mode = match self.projection.mode {
+      ProjectionMode::Perspective => ProjectionMode::Orthographic,
+      ProjectionMode::Orthographic => ProjectionMode::Perspective,
+    };
+    self.has_changed = true;
+  }
+
+  // Both modes look from the eye to the center, so switching keeps the current view. Fly mode
+  // keeps the center at the same distance in front of the eye, which is what orbit mode
+  // circles around once switched back.
+  pub fn toggle_mode(&mut self) {
+    self.mode = match self.mode {
+      CameraMode::Orbit => CameraMode::Fly,
+      CameraMode::Fly => CameraMode::Orbit,
+    };
+    self.has_changed = true;
+  }
+
   pub fn basis_change(&self, vector: &Vec3) -> Vec3 {
     let forward = (self.center - self.eye).normalize();
     let right = forward.cross(&self.up).normalize();
@@ -32,7 +147,144 @@ impl Camera {
     rotated.normalize()
   }
 
+  // Rotation from camera space, where the camera looks down -Z with +Y up, to world space
+  pub fn orientation(&self) -> Quat {
+    let forward = (self.center - self.eye).normalize();
+    let right = forward.cross(&self.up).normalize();
+    let up = right.cross(&forward);
+    mat3_to_quat(&Mat3::from_columns(&[right, up, -forward]))
+  }
+
+  // Turns the view to the orientation, around the center in orbit mode and around the eye when
+  // flying
+  pub fn set_orientation(&mut self, orientation: &Quat) {
+    let distance = self.distance();
+    let basis = quat_to_mat3(orientation);
+    let forward = -basis.column(2).into_owned();
+
+    match self.mode {
+      CameraMode::Orbit => self.eye = self.center - forward * distance,
+      CameraMode::Fly => self.center = self.eye + forward * distance,
+    }
+    self.up = basis.column(1).into_owned();
+    self.has_changed = true;
+  }
+
+  // Applies a world space rotation to the current orientation
+  fn rotate(&mut self, rotation: &Quat) {
+    let orientation = rotation * self.orientation();
+    self.set_orientation(&orientation);
+  }
+
+  // Arcball turn, yaw around the camera up and pitch around its right axis, with no limits
+  fn turn(&mut self, delta_yaw: f32, delta_pitch: f32) {
+    let forward = (self.center - self.eye).normalize();
+    let right = forward.cross(&self.up).normalize();
+    let up = right.cross(&forward);
+    self.rotate(&(quat_angle_axis(-delta_yaw, &up) * quat_angle_axis(delta_pitch, &right)));
+  }
+
+  pub fn toggle_free_rotation(&mut self) {
+    self.free_rotation = !self.free_rotation;
+    // The yaw and pitch controls expect the world up, turn back upright before using them
+    if !self.free_rotation {
+      self.level();
+    }
+  }
+
+  // Turns around the view direction, positive angles roll clockwise
+  pub fn roll(&mut self, angle: f32) {
+    self.transition = None;
+    let forward = (self.center - self.eye).normalize();
+    self.rotate(&quat_angle_axis(angle, &forward));
+  }
+
+  // Starts rolling the camera back so the world Y axis points up on screen, the view direction
+  // stays the same and update finishes the turn over the next frames
+  pub fn level(&mut self) {
+    let forward = (self.center - self.eye).normalize();
+    let world_up = Vec3::new(0.0, 1.0, 0.0);
+    // Straight up or down there is no horizon to level
+    if forward.cross(&world_up).magnitude() < 1e-3 {
+      return;
+    }
+    let right = forward.cross(&world_up).normalize();
+    let up = right.cross(&forward);
+    let target = mat3_to_quat(&Mat3::from_columns(&[right, up, -forward]));
+    self.transition = Some((self.orientation(), target, 0.0));
+  }
+
+  // Moves the center to the sphere and backs off until it fits the view with a small margin,
+  // keeping the view direction. Update gets there smoothly over the next frames.
+  pub fn focus(&mut self, center: Vec3, radius: f32, aspect_ratio: f32) {
+    let margin = 1.2;
+    let half_fov = (self.projection.fov * 0.5).min((aspect_ratio * (self.projection.fov * 0.5).tan()).atan());
+    let distance = (radius * margin / half_fov.sin()).max(self.projection.near + radius);
+    self.focus = Some(Focus { center, distance: Some(distance) });
+  }
+
+  // Keeps a focused camera on a moving target
+  pub fn follow(&mut self, center: Vec3) {
+    if let Some(focus) = &mut self.focus {
+      focus.center = center;
+    }
+  }
+
+  pub fn unfocus(&mut self) {
+    self.focus = None;
+  }
+
+  pub fn has_focus(&self) -> bool {
+    self.focus.is_some()
+  }
+
+  // Advances running focus and orientation transitions, delta in seconds
+  pub fn update(&mut self, delta: f32) {
+    if let Some(focus) = &mut self.focus {
+      // Exponential approach, frame rate independent and smooth when the target moves
+      let blend = 1.0 - (-delta * 6.0).exp();
+      let forward = (self.center - self.eye).normalize();
+      let distance = (self.center - self.eye).magnitude();
+
+      let new_distance = match focus.distance {
+        Some(target) if (target - distance).abs() < 1e-3 => {
+          focus.distance = None;
+          target
+        }
+        Some(target) => distance + (target - distance) * blend,
+        None => distance,
+      };
+      let new_center = self.center + (focus.center - self.center) * blend;
+
+      if new_center != self.center || new_distance != distance {
+        self.center = new_center;
+        self.eye = new_center - forward * new_distance;
+        self.has_changed = true;
+      }
+    }
+
+    let transition_time = 0.5;
+    if let Some((from, to, progress)) = self.transition {
+      let progress = (progress + delta / transition_time).min(1.0);
+      let eased = progress * progress * (3.0 - 2.0 * progress);
+      self.set_orientation(&quat_slerp(&from, &to, eased));
+      self.transition = if progress < 1.0 { Some((from, to, progress)) } else { None };
+      // Same view, but the yaw and pitch controls need the world up itself
+      if self.transition.is_none() && !self.free_rotation {
+        self.up = Vec3::new(0.0, 1.0, 0.0);
+      }
+    }
+  }
+
+  // Turning by hand takes over from a running transition
   pub fn orbit(&mut self, delta_yaw: f32, delta_pitch: f32) {
+    self.transition = None;
+    if self.free_rotation {
+      self.turn(delta_yaw, delta_pitch);
+      return;
+    }
+    self.up = Vec3::new(0.0, 1.0, 0.0);
+
     let radius_vector = self.eye - self.center;
     let radius = radius_vector.magnitude();
 
@@ -54,13 +306,82 @@ impl Camera {
     self.has_changed = true;
   }
 
+  // Moves the eye towards the center, stopping short of it so the view direction stays defined
   pub fn zoom(&mut self, delta: f32) {
-    let direction = (self.center - self.eye).normalize();
-    self.eye += direction * delta;
+    let min_distance = 0.2;
+    let offset = self.center - self.eye;
+    let distance = offset.magnitude();
+    let new_distance = (distance - delta).max(min_distance);
+
+    self.eye = self.center - offset / distance * new_distance;
+    self.has_changed = true;
+  }
+
+  // Approximate height in pixels of a sphere on a screen of the given height
+  pub fn projected_size(&self, center: &Vec3, radius: f32, screen_height: f32) -> f32 {
+    let distance = match self.projection.mode {
+      ProjectionMode::Perspective => (center - self.eye).magnitude().max(radius),
+      // The orthographic view spans as much as the perspective one at the center distance
+      ProjectionMode::Orthographic => self.distance(),
+    };
+    radius * screen_height / (distance * (self.projection.fov * 0.5).tan())
+  }
+
+  pub fn distance(&self) -> f32 {
+    (self.center - self.eye).magnitude()
+  }
+
+  // Slides eye and center together across the view plane
+  pub fn pan(&mut self, delta_right: f32, delta_up: f32) {
+    let forward = (self.center - self.eye).normalize();
+    let right = forward.cross(&self.up).normalize();
+    let up = right.cross(&forward).normalize();
+
+    // Moving the center by hand stops following the focused object
+    self.focus = None;
+    let offset = right * delta_right + up * delta_up;
+    self.eye += offset;
+    self.center += offset;
+    self.has_changed = true;
+  }
+
+  // Turns the view around the eye, positive yaw to the right and positive pitch upwards
+  pub fn look(&mut self, delta_yaw: f32, delta_pitch: f32) {
+    self.transition = None;
+    self.focus = None;
+    if self.free_rotation {
+      self.turn(delta_yaw, delta_pitch);
+      return;
+    }
+    self.up = Vec3::new(0.0, 1.0, 0.0);
+
+    let offset = self.center - self.eye;
+    let distance = offset.magnitude();
+    let direction = offset / distance;
+
+    let yaw = direction.z.atan2(direction.x) + delta_yaw;
+    let pitch = (direction.y.clamp(-1.0, 1.0).asin() + delta_pitch).clamp(-PI / 2.0 + 0.1, PI / 2.0 - 0.1);
+
+    let new_direction = Vec3::new(yaw.cos() * pitch.cos(), pitch.sin(), yaw.sin() * pitch.cos());
+    self.center = self.eye + new_direction * distance;
+    self.has_changed = true;
+  }
+
+  // Moves eye and center together, x along the view right, y along the camera up and z forward
+  pub fn fly(&mut self, movement: Vec3) {
+    let forward = (self.center - self.eye).normalize();
+    let right = forward.cross(&self.up).normalize();
+
+    self.focus = None;
+    let offset = right * movement.x + self.up * movement.y + forward * movement.z;
+    self.eye += offset;
+    self.center += offset;
     self.has_changed = true;
   }
 
   pub fn move_center(&mut self, direction: Vec3) {
+    self.transition = None;
+    self.focus = None;
     let radius_vector = self.center - self.eye;
     let radius = radius_vector.magnitude();
 
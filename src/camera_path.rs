@@ -0,0 +1,206 @@
+
+use nalgebra_glm::Vec3;
+use std::fs;
+use std::io;
+use crate::camera::Camera;
+
+// Camera state at one point of a path, time in seconds from the start of the path
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe {
+    pub time: f32,
+    pub eye: Vec3,
+    pub center: Vec3,
+    pub up: Vec3,
+    // Vertical field of view in radians
+    pub fov: f32,
+}
+
+impl Keyframe {
+    pub fn from_camera(camera: &Camera, time: f32) -> Self {
+        Keyframe {
+            time,
+            eye: camera.eye,
+            center: camera.center,
+            up: camera.up,
+            fov: camera.projection.fov,
+        }
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.eye = self.eye;
+        camera.center = self.center;
+        camera.up = self.up;
+        camera.projection.fov = self.fov;
+        camera.has_changed = true;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    // Starts and stops gently, applied over the whole path rather than every segment so the
+    // camera does not slow down at each keyframe
+    EaseInOut,
+}
+
+impl Easing {
+    fn name(&self) -> &'static str {
+        match self {
+            Easing::Linear => "linear",
+            Easing::EaseInOut => "ease-in-out",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "ease-in-out" => Some(Easing::EaseInOut),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+// Keyframes played back along Catmull-Rom splines, which pass through every keyframe
+pub struct CameraPath {
+    pub keyframes: Vec<Keyframe>,
+    pub easing: Easing,
+    // Time between keyframes recorded with record
+    pub segment_duration: f32,
+}
+
+impl CameraPath {
+    pub fn new() -> Self {
+        CameraPath {
+            keyframes: Vec::new(),
+            easing: Easing::EaseInOut,
+            segment_duration: 3.0,
+        }
+    }
+
+    // Appends the current view one segment after the last keyframe
+    pub fn record(&mut self, camera: &Camera) {
+        let time = self.keyframes.last().map_or(0.0, |last| last.time + self.segment_duration);
+        self.keyframes.push(Keyframe::from_camera(camera, time));
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |last| last.time)
+    }
+
+    // Camera state at the given time, clamped to the ends of the path
+    pub fn sample(&self, time: f32) -> Option<Keyframe> {
+        let first = self.keyframes.first()?;
+        if self.keyframes.len() == 1 || self.duration() <= first.time {
+            return Some(*first);
+        }
+
+        let progress = ((time - first.time) / (self.duration() - first.time)).clamp(0.0, 1.0);
+        let time = first.time + self.easing.apply(progress) * (self.duration() - first.time);
+
+        // Segment between keyframes i and i + 1, the ends repeat to give the spline its tangents
+        let last = self.keyframes.len() - 1;
+        let i = self.keyframes[1..].iter().position(|k| k.time >= time).unwrap_or(last - 1);
+        let (k1, k2) = (&self.keyframes[i], &self.keyframes[i + 1]);
+        let k0 = &self.keyframes[i.saturating_sub(1)];
+        let k3 = &self.keyframes[(i + 2).min(last)];
+
+        let length = k2.time - k1.time;
+        let t = if length > 0.0 { ((time - k1.time) / length).clamp(0.0, 1.0) } else { 1.0 };
+
+        Some(Keyframe {
+            time,
+            eye: catmull_rom(&k0.eye, &k1.eye, &k2.eye, &k3.eye, t),
+            center: catmull_rom(&k0.center, &k1.center, &k2.center, &k3.center, t),
+            // Opposite up vectors blend through zero, keep the keyframe one rather than dividing by it
+            up: catmull_rom(&k0.up, &k1.up, &k2.up, &k3.up, t).try_normalize(1e-6).unwrap_or(k1.up),
+            fov: catmull_rom_scalar(k0.fov, k1.fov, k2.fov, k3.fov, t),
+        })
+    }
+
+    // An easing line, then one keyframe per line: time, eye, center and up vectors and the field
+    // of view in degrees
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let mut text = format!("easing {}\n", self.easing.name());
+        text.push_str("# time eye.x eye.y eye.z center.x center.y center.z up.x up.y up.z fov\n");
+        for k in &self.keyframes {
+            text.push_str(&format!(
+                "{} {} {} {} {} {} {} {} {} {} {}\n",
+                k.time, k.eye.x, k.eye.y, k.eye.z, k.center.x, k.center.y, k.center.z,
+                k.up.x, k.up.y, k.up.z, k.fov.to_degrees()
+            ));
+        }
+        fs::write(filename, text)
+    }
+
+    pub fn load(filename: &str) -> io::Result<Self> {
+        let text = fs::read_to_string(filename).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", filename, e)))?;
+        let invalid = |line: usize, message: &str| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: {}", filename, line, message))
+        };
+
+        let mut path = CameraPath::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix("easing ") {
+                path.easing = Easing::from_name(name.trim()).ok_or_else(|| invalid(index + 1, "unknown easing"))?;
+                continue;
+            }
+            let values = line
+                .split_whitespace()
+                .map(|value| value.parse::<f32>())
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|_| invalid(index + 1, "not a number"))?;
+            if values.len() != 11 {
+                return Err(invalid(index + 1, "expected 11 values"));
+            }
+            if values.iter().any(|value| !value.is_finite()) {
+                return Err(invalid(index + 1, "not a finite number"));
+            }
+
+            let keyframe = Keyframe {
+                time: values[0],
+                eye: Vec3::new(values[1], values[2], values[3]),
+                center: Vec3::new(values[4], values[5], values[6]),
+                up: Vec3::new(values[7], values[8], values[9]),
+                fov: values[10].to_radians(),
+            };
+            if keyframe.up.magnitude() == 0.0 {
+                return Err(invalid(index + 1, "up vector is zero"));
+            }
+            if path.keyframes.last().is_some_and(|last| keyframe.time < last.time) {
+                return Err(invalid(index + 1, "keyframes have to be in time order"));
+            }
+            path.keyframes.push(keyframe);
+        }
+
+        Ok(path)
+    }
+}
+
+fn catmull_rom(p0: &Vec3, p1: &Vec3, p2: &Vec3, p3: &Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3) * 0.5
+}
+
+fn catmull_rom_scalar(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3) * 0.5
+}
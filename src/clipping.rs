@@ -0,0 +1,81 @@
+
+use nalgebra_glm::{Vec3, Vec4};
+use crate::vertex::Vertex;
+use crate::Uniforms;
+
+// The frustum in clip space, a point is inside every plane where dot(plane, clip) >= 0
+const PLANES: [Vec4; 6] = [
+    Vec4::new(1.0, 0.0, 0.0, 1.0),
+    Vec4::new(-1.0, 0.0, 0.0, 1.0),
+    Vec4::new(0.0, 1.0, 0.0, 1.0),
+    Vec4::new(0.0, -1.0, 0.0, 1.0),
+    Vec4::new(0.0, 0.0, 1.0, 1.0),
+    Vec4::new(0.0, 0.0, -1.0, 1.0),
+];
+
+// Clips a triangle from the vertex shader against the view frustum, in clip space before the
+// perspective divide. Without it, triangles reaching behind the near plane project through the
+// eye into huge or mirrored shapes. The clipped polygon comes back as a fan of triangles with
+// their screen positions recomputed.
+pub fn clip_triangle(triangle: &[Vertex], uniforms: &Uniforms) -> Vec<[Vertex; 3]> {
+    let clip = |vertex: &Vertex| {
+        let view = vertex.view_position;
+        uniforms.projection_matrix * Vec4::new(view.x, view.y, view.z, 1.0)
+    };
+    let mut polygon: Vec<(Vec4, Vertex)> = triangle.iter().map(|vertex| (clip(vertex), vertex.clone())).collect();
+
+    // Most triangles are fully inside and keep the positions the vertex shader gave them
+    if polygon.iter().all(|(position, _)| PLANES.iter().all(|plane| plane.dot(position) >= 0.0)) {
+        return vec![[triangle[0].clone(), triangle[1].clone(), triangle[2].clone()]];
+    }
+
+    // Sutherland-Hodgman, one plane at a time
+    for plane in &PLANES {
+        let mut clipped = Vec::with_capacity(polygon.len() + 1);
+        for i in 0..polygon.len() {
+            let (a, b) = (&polygon[i], &polygon[(i + 1) % polygon.len()]);
+            let (da, db) = (plane.dot(&a.0), plane.dot(&b.0));
+            if da >= 0.0 {
+                clipped.push(a.clone());
+            }
+            if (da >= 0.0) != (db >= 0.0) {
+                let t = da / (da - db);
+                clipped.push((a.0 + (b.0 - a.0) * t, lerp_vertex(&a.1, &b.1, t)));
+            }
+        }
+        polygon = clipped;
+        if polygon.len() < 3 {
+            return Vec::new();
+        }
+    }
+
+    let vertices: Vec<Vertex> = polygon.into_iter().map(|(position, mut vertex)| {
+        let ndc = Vec4::new(position.x / position.w, position.y / position.w, position.z / position.w, 1.0);
+        let screen = uniforms.viewport_matrix * ndc;
+        vertex.transformed_position = Vec3::new(screen.x, screen.y, screen.z);
+        vertex
+    }).collect();
+
+    (1..vertices.len() - 1)
+        .map(|i| [vertices[0].clone(), vertices[i].clone(), vertices[i + 1].clone()])
+        .collect()
+}
+
+// Every attribute is affine in clip space, so a straight blend is exact before the divide
+fn lerp_vertex(a: &Vertex, b: &Vertex, t: f32) -> Vertex {
+    Vertex {
+        position: a.position.lerp(&b.position, t),
+        normal: a.normal.lerp(&b.normal, t),
+        tex_coords: a.tex_coords.lerp(&b.tex_coords, t),
+        tangent: a.tangent.lerp(&b.tangent, t),
+        bitangent: a.bitangent.lerp(&b.bitangent, t),
+        color: a.color.lerp(&b.color, t),
+        transformed_position: a.transformed_position,
+        world_position: a.world_position.lerp(&b.world_position, t),
+        world_normal: a.world_normal.lerp(&b.world_normal, t),
+        view_position: a.view_position.lerp(&b.view_position, t),
+        view_normal: a.view_normal.lerp(&b.view_normal, t),
+        world_tangent: a.world_tangent.lerp(&b.world_tangent, t),
+        world_bitangent: a.world_bitangent.lerp(&b.world_bitangent, t),
+    }
+}
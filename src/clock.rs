@@ -0,0 +1,99 @@
+
+use std::time::Instant;
+
+enum Source {
+    // Wall clock, animation speed does not depend on the frame rate
+    RealTime { last: Instant },
+    // Same step every frame, renders are reproducible whatever the machine
+    FixedStep { step: f64 },
+}
+
+// Simulation time in seconds, advanced once per frame with tick
+pub struct Clock {
+    source: Source,
+    elapsed: f64,
+    delta: f64,
+    // Unscaled length of the last frame, it keeps running while paused
+    frame_time: f64,
+    pub paused: bool,
+    pub time_scale: f64,
+    step_requested: bool,
+}
+
+impl Clock {
+    // Longest real frame that counts, so a stall like dragging the window does not make
+    // everything jump ahead
+    const MAX_DELTA: f64 = 0.1;
+    // Length of a single step while paused
+    const STEP: f64 = 1.0 / 60.0;
+
+    pub fn real_time() -> Self {
+        Clock::with_source(Source::RealTime { last: Instant::now() })
+    }
+
+    pub fn fixed_step(step: f64) -> Self {
+        Clock::with_source(Source::FixedStep { step })
+    }
+
+    fn with_source(source: Source) -> Self {
+        Clock {
+            source,
+            elapsed: 0.0,
+            delta: 0.0,
+            frame_time: 0.0,
+            paused: false,
+            time_scale: 1.0,
+            step_requested: false,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        let raw = match &mut self.source {
+            Source::RealTime { last } => {
+                let now = Instant::now();
+                let raw = now.duration_since(*last).as_secs_f64().min(Clock::MAX_DELTA);
+                *last = now;
+                raw
+            }
+            Source::FixedStep { step } => *step,
+        };
+
+        self.frame_time = raw;
+        self.delta = if self.step_requested {
+            self.step_requested = false;
+            Clock::STEP * self.time_scale
+        } else if self.paused {
+            0.0
+        } else {
+            raw * self.time_scale
+        };
+        self.elapsed += self.delta;
+    }
+
+    // Seconds since the start, accumulated in f64 so long runs keep their precision
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed as f32
+    }
+
+    pub fn delta(&self) -> f32 {
+        self.delta as f32
+    }
+
+    // For things that move with the viewer rather than the simulation, like the camera
+    pub fn frame_time(&self) -> f32 {
+        self.frame_time as f32
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    // Advances a single frame on the next tick, meant for stepping while paused
+    pub fn step(&mut self) {
+        self.step_requested = true;
+    }
+
+    pub fn scale_time(&mut self, factor: f64) {
+        self.time_scale = (self.time_scale * factor).clamp(1.0 / 64.0, 64.0);
+    }
+}
@@ -1,4 +1,5 @@
 use std::fmt;
+use nalgebra_glm::Vec3;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Color {
@@ -19,6 +20,26 @@ impl Color {
         Color { r, g, b }
     }
 
+    // Parses "#rrggbb", "rrggbb", "0xrrggbb" or the short "#rgb" form
+    pub fn from_hex_str(hex: &str) -> Option<Self> {
+        let digits = hex.trim();
+        let digits = digits.strip_prefix('#')
+            .or_else(|| digits.strip_prefix("0x"))
+            .unwrap_or(digits);
+
+        match digits.len() {
+            6 => u32::from_str_radix(digits, 16).ok().map(Color::from_hex),
+            3 => {
+                let short = u32::from_str_radix(digits, 16).ok()?;
+                let r = ((short >> 8) & 0xF) * 0x11;
+                let g = ((short >> 4) & 0xF) * 0x11;
+                let b = (short & 0xF) * 0x11;
+                Some(Color::new(r as u8, g as u8, b as u8))
+            }
+            _ => None,
+        }
+    }
+
     pub const fn black() -> Self {
         Color { r: 0, g: 0, b: 0 }
     }
@@ -27,6 +48,122 @@ impl Color {
         ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32)
     }
 
+    // Channels mapped to the 0.0 - 1.0 range
+    pub fn to_vec3(self) -> Vec3 {
+        Vec3::new(self.r as f32 / 255.0, self.g as f32 / 255.0, self.b as f32 / 255.0)
+    }
+
+    pub fn from_vec3(v: &Vec3) -> Self {
+        Color {
+            r: (v.x * 255.0).round().clamp(0.0, 255.0) as u8,
+            g: (v.y * 255.0).round().clamp(0.0, 255.0) as u8,
+            b: (v.z * 255.0).round().clamp(0.0, 255.0) as u8,
+        }
+    }
+
+    // sRGB channels decoded to linear light, where adding and averaging is physically right
+    pub fn to_linear(self) -> Vec3 {
+        let c = self.to_vec3();
+        Vec3::new(srgb_to_linear(c.x), srgb_to_linear(c.y), srgb_to_linear(c.z))
+    }
+
+    pub fn from_linear(linear: &Vec3) -> Self {
+        Color::from_vec3(&Vec3::new(linear_to_srgb(linear.x), linear_to_srgb(linear.y), linear_to_srgb(linear.z)))
+    }
+
+    // OKLab (L, a, b), perceptually uniform so blends keep an even brightness
+    pub fn to_oklab(self) -> Vec3 {
+        let c = self.to_linear();
+        let l = (0.41222147 * c.x + 0.53633254 * c.y + 0.051445993 * c.z).cbrt();
+        let m = (0.2119035 * c.x + 0.6806995 * c.y + 0.10739696 * c.z).cbrt();
+        let s = (0.08830246 * c.x + 0.28171884 * c.y + 0.6299787 * c.z).cbrt();
+
+        Vec3::new(
+            0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+            1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+            0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+        )
+    }
+
+    pub fn from_oklab(lab: &Vec3) -> Self {
+        let l = lab.x + 0.39633778 * lab.y + 0.21580376 * lab.z;
+        let m = lab.x - 0.105561346 * lab.y - 0.06385417 * lab.z;
+        let s = lab.x - 0.08948418 * lab.y - 1.2914855 * lab.z;
+        let (l, m, s) = (l * l * l, m * m * m, s * s * s);
+
+        Color::from_linear(&Vec3::new(
+            4.0767417 * l - 3.3077116 * m + 0.23096993 * s,
+            -1.268438 * l + 2.6097574 * m - 0.341_319_38 * s,
+            -0.0041960863 * l - 0.7034186 * m + 1.7076147 * s,
+        ))
+    }
+
+    // (hue in degrees 0..360, saturation 0..1, value 0..1)
+    pub fn to_hsv(self) -> Vec3 {
+        let c = self.to_vec3();
+        let max = c.max();
+        let min = c.min();
+        let delta = max - min;
+
+        let saturation = if max > 0.0 { delta / max } else { 0.0 };
+        Vec3::new(hue(&c, max, delta), saturation, max)
+    }
+
+    pub fn from_hsv(hsv: &Vec3) -> Self {
+        let s = hsv.y.clamp(0.0, 1.0);
+        let v = hsv.z.clamp(0.0, 1.0);
+        let chroma = v * s;
+        Color::from_vec3(&(hue_to_rgb(hsv.x, chroma) + Vec3::repeat(v - chroma)))
+    }
+
+    // (hue in degrees 0..360, saturation 0..1, lightness 0..1)
+    pub fn to_hsl(self) -> Vec3 {
+        let c = self.to_vec3();
+        let max = c.max();
+        let min = c.min();
+        let delta = max - min;
+        let lightness = (max + min) * 0.5;
+
+        let saturation = if delta > 0.0 { delta / (1.0 - (2.0 * lightness - 1.0).abs()) } else { 0.0 };
+        Vec3::new(hue(&c, max, delta), saturation, lightness)
+    }
+
+    pub fn from_hsl(hsl: &Vec3) -> Self {
+        let s = hsl.y.clamp(0.0, 1.0);
+        let l = hsl.z.clamp(0.0, 1.0);
+        let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        Color::from_vec3(&(hue_to_rgb(hsl.x, chroma) + Vec3::repeat(l - chroma * 0.5)))
+    }
+
+    // Rotates the hue, wrapping around the color wheel
+    pub fn shift_hue(self, degrees: f32) -> Self {
+        let mut hsv = self.to_hsv();
+        hsv.x += degrees;
+        Color::from_hsv(&hsv)
+    }
+
+    // Scales the saturation, 0 gives grey and values above 1 make colors more vivid
+    pub fn saturate(self, factor: f32) -> Self {
+        let mut hsl = self.to_hsl();
+        hsl.y *= factor;
+        Color::from_hsl(&hsl)
+    }
+
+    // Scales the value keeping hue and saturation, unlike multiplying that clamps each channel
+    pub fn brighten(self, factor: f32) -> Self {
+        let mut hsv = self.to_hsv();
+        hsv.z *= factor;
+        Color::from_hsv(&hsv)
+    }
+
+    // Blend in OKLab, no muddy or too dark midpoints between very different hues
+    pub fn lerp_perceptual(&self, other: &Color, t: f32) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let a = self.to_oklab();
+        let b = other.to_oklab();
+        Color::from_oklab(&(a + (b - a) * t))
+    }
+
     // Linear interpolation between two colors
     pub fn lerp(&self, other: &Color, t: f32) -> Self {
         let t = t.clamp(0.0, 1.0);
@@ -70,6 +207,53 @@ impl Color {
     }
 }
 
+// Hue in degrees of a 0..1 RGB color, shared by HSV and HSL
+fn hue(c: &Vec3, max: f32, delta: f32) -> f32 {
+    if delta == 0.0 {
+        return 0.0;
+    }
+    let sector = if max == c.x {
+        ((c.y - c.z) / delta).rem_euclid(6.0)
+    } else if max == c.y {
+        (c.z - c.x) / delta + 2.0
+    } else {
+        (c.x - c.y) / delta + 4.0
+    };
+    sector * 60.0
+}
+
+// Fully saturated RGB for a hue, scaled by chroma and before adding the grey level
+fn hue_to_rgb(hue: f32, chroma: f32) -> Vec3 {
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+
+    match sector as u32 {
+        0 => Vec3::new(chroma, x, 0.0),
+        1 => Vec3::new(x, chroma, 0.0),
+        2 => Vec3::new(0.0, chroma, x),
+        3 => Vec3::new(0.0, x, chroma),
+        4 => Vec3::new(x, 0.0, chroma),
+        _ => Vec3::new(chroma, 0.0, x),
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
 use std::ops::Add;
 
 impl Add for Color {
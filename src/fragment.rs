@@ -9,6 +9,13 @@ pub struct Fragment {
     pub normal: Vec3,
     pub intensity: f32,
     pub vertex_position: Vec3,
+    pub world_position: Vec3,
+    pub view_position: Vec3,
+    pub view_normal: Vec3,
+    pub tex_coords: Vec2,
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
+    pub shadow: f32,
 }
 
 impl Fragment {
@@ -19,7 +26,14 @@ impl Fragment {
             depth,
             normal,
             intensity,
-            vertex_position
+            vertex_position,
+            world_position: vertex_position,
+            view_position: vertex_position,
+            view_normal: normal,
+            tex_coords: Vec2::new(0.0, 0.0),
+            tangent: Vec3::new(1.0, 0.0, 0.0),
+            bitangent: Vec3::new(0.0, 0.0, 1.0),
+            shadow: 1.0
         }
     }
-}
\ No newline at end of file
+}
@@ -1,3 +1,7 @@
+use std::fs;
+use std::io::{self, Write};
+use crate::color::Color;
+
 
 pub struct Framebuffer {
     pub width: usize,
@@ -20,6 +24,18 @@ impl Framebuffer {
         }
     }
 
+    // Framebuffer without a color buffer, used for depth-only passes like shadow maps
+    pub fn new_depth_only(width: usize, height: usize) -> Self {
+        Framebuffer {
+            width,
+            height,
+            buffer: Vec::new(),
+            zbuffer: vec![f32::INFINITY; width * height],
+            background_color: 0x000000,
+            current_color: 0xFFFFFF
+        }
+    }
+
     pub fn clear(&mut self) {
         for pixel in self.buffer.iter_mut() {
             *pixel = self.background_color;
@@ -40,6 +56,58 @@ impl Framebuffer {
         }
     }
 
+    // Background pixels like the sky, only drawn where no geometry has been drawn yet
+    pub fn background_point(&mut self, x: usize, y: usize, color: u32) {
+        if x < self.width && y < self.height {
+            let index = y * self.width + x;
+
+            if self.zbuffer[index] == f32::INFINITY {
+                self.buffer[index] = color;
+            }
+        }
+    }
+
+    pub fn color_at(&self, x: usize, y: usize) -> u32 {
+        self.buffer[y * self.width + x]
+    }
+
+    // Alpha blended pixel for transparent surfaces, depth tested but not written so
+    // whatever is behind stays visible
+    pub fn blend_point(&mut self, x: usize, y: usize, depth: f32, color: Color, alpha: f32) {
+        if x < self.width && y < self.height {
+            let index = y * self.width + x;
+
+            if self.zbuffer[index] > depth {
+                let behind = Color::from_hex(self.buffer[index]);
+                self.buffer[index] = behind.lerp(&color, alpha).to_hex();
+            }
+        }
+    }
+
+    pub fn point_depth(&mut self, x: usize, y: usize, depth: f32) {
+        if x < self.width && y < self.height {
+            let index = y * self.width + x;
+
+            if self.zbuffer[index] > depth {
+                self.zbuffer[index] = depth;
+            }
+        }
+    }
+
+    pub fn depth_at(&self, x: usize, y: usize) -> f32 {
+        self.zbuffer[y * self.width + x]
+    }
+
+    // Saves the color buffer as a binary PPM (P6) image
+    pub fn save_ppm(&self, filename: &str) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(filename)?);
+        write!(file, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for pixel in &self.buffer {
+            file.write_all(&[(pixel >> 16) as u8, (pixel >> 8) as u8, *pixel as u8])?;
+        }
+        file.flush()
+    }
+
     pub fn set_background_color(&mut self, color: u32) {
         self.background_color = color;
     }
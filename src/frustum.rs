@@ -0,0 +1,115 @@
+
+use nalgebra_glm::{Vec3, Vec4, Mat4};
+
+// Axis-aligned bounding box
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Vec3>) -> Self {
+        let mut min = Vec3::repeat(f32::INFINITY);
+        let mut max = Vec3::repeat(f32::NEG_INFINITY);
+        for point in points {
+            min = min.inf(point);
+            max = max.sup(point);
+        }
+        Aabb { min, max }
+    }
+
+    pub fn grow(&self, amount: f32) -> Self {
+        Aabb { min: self.min.add_scalar(-amount), max: self.max.add_scalar(amount) }
+    }
+
+    // Box around the transformed corners, it stays conservative under rotation
+    pub fn transform(&self, matrix: &Mat4) -> Self {
+        let corners: Vec<Vec3> = (0..8).map(|i| {
+            let corner = Vec3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            );
+            let transformed = matrix * Vec4::new(corner.x, corner.y, corner.z, 1.0);
+            Vec3::new(transformed.x, transformed.y, transformed.z)
+        }).collect();
+        Aabb::from_points(&corners)
+    }
+}
+
+// Result of testing a bounding volume against the frustum
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Visibility {
+    Outside,
+    // Crosses at least one plane, smaller parts of it may still be culled
+    Intersecting,
+    Inside,
+}
+
+// The six planes bounding what the camera sees, in world space with normals pointing inwards
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    // Planes taken straight from the rows of projection * view, they hold for perspective and
+    // orthographic projections alike
+    pub fn from_matrix(view_projection: &Mat4) -> Self {
+        let row = |i: usize| view_projection.row(i).transpose();
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(3) + row(2),
+            row(3) - row(2),
+        ].map(|plane| plane / plane.xyz().magnitude());
+        Frustum { planes }
+    }
+
+    pub fn test_sphere(&self, center: &Vec3, radius: f32) -> Visibility {
+        let mut visibility = Visibility::Inside;
+        for plane in &self.planes {
+            let distance = plane.xyz().dot(center) + plane.w;
+            if distance < -radius {
+                return Visibility::Outside;
+            }
+            if distance < radius {
+                visibility = Visibility::Intersecting;
+            }
+        }
+        visibility
+    }
+
+    // Only the corner furthest along each plane normal needs checking, a box is outside when
+    // even that corner is behind one of the planes
+    pub fn test_aabb(&self, aabb: &Aabb) -> Visibility {
+        let mut visibility = Visibility::Inside;
+        for plane in &self.planes {
+            let normal = plane.xyz();
+            let corner = |towards: bool| Vec3::new(
+                if (normal.x >= 0.0) == towards { aabb.max.x } else { aabb.min.x },
+                if (normal.y >= 0.0) == towards { aabb.max.y } else { aabb.min.y },
+                if (normal.z >= 0.0) == towards { aabb.max.z } else { aabb.min.z },
+            );
+            if normal.dot(&corner(true)) + plane.w < 0.0 {
+                return Visibility::Outside;
+            }
+            if normal.dot(&corner(false)) + plane.w < 0.0 {
+                visibility = Visibility::Intersecting;
+            }
+        }
+        visibility
+    }
+}
+
+// How much the last frame drew and skipped
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CullStats {
+    pub objects_drawn: usize,
+    pub objects_culled: usize,
+    pub clusters_drawn: usize,
+    pub clusters_culled: usize,
+    pub triangles: usize,
+}
@@ -0,0 +1,103 @@
+
+use nalgebra_glm::Vec3;
+use crate::color::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    Smoothstep,
+    // Hard steps, every stop holds its color until the next one
+    Constant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendSpace {
+    // Linear light, physically correct mixes
+    Linear,
+    // OKLab, even perceived brightness along the ramp
+    Perceptual,
+}
+
+// Color ramp mapping a 0..1 scalar, like a noise value, to a color
+#[derive(Debug, Clone)]
+pub struct Gradient {
+    stops: Vec<(f32, Color)>,
+    pub interpolation: Interpolation,
+    pub space: BlendSpace,
+}
+
+impl Gradient {
+    pub fn new(stops: &[(f32, Color)]) -> Self {
+        let mut stops = stops.to_vec();
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        Gradient {
+            stops,
+            interpolation: Interpolation::Linear,
+            space: BlendSpace::Linear,
+        }
+    }
+
+    // Stops given as (position, "#rrggbb")
+    pub fn from_hex_stops(stops: &[(f32, &str)]) -> Result<Self, String> {
+        let parsed = stops.iter()
+            .map(|(t, hex)| Color::from_hex_str(hex).map(|c| (*t, c)).ok_or_else(|| format!("invalid hex color '{}'", hex)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Gradient::new(&parsed))
+    }
+
+    // Colors spread evenly over 0..1
+    pub fn from_hex(colors: &[&str]) -> Result<Self, String> {
+        let last = colors.len().saturating_sub(1).max(1) as f32;
+        let stops: Vec<(f32, &str)> = colors.iter().enumerate()
+            .map(|(i, hex)| (i as f32 / last, *hex))
+            .collect();
+
+        Gradient::from_hex_stops(&stops)
+    }
+
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    pub fn in_space(mut self, space: BlendSpace) -> Self {
+        self.space = space;
+        self
+    }
+
+    pub fn sample(&self, t: f32) -> Color {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Color::black(),
+        };
+        // NaN fails both comparisons below and would find no segment
+        if t <= first.0 || t.is_nan() {
+            return first.1;
+        }
+        if t >= last.0 {
+            return last.1;
+        }
+
+        let next = self.stops.iter().position(|stop| stop.0 > t).unwrap_or(self.stops.len() - 1);
+        let (t0, c0) = self.stops[next - 1];
+        let (t1, c1) = self.stops[next];
+
+        let local = if t1 > t0 { (t - t0) / (t1 - t0) } else { 0.0 };
+        let local = match self.interpolation {
+            Interpolation::Linear => local,
+            Interpolation::Smoothstep => local * local * (3.0 - 2.0 * local),
+            Interpolation::Constant => return c0,
+        };
+
+        match self.space {
+            BlendSpace::Linear => Color::from_linear(&mix(&c0.to_linear(), &c1.to_linear(), local)),
+            BlendSpace::Perceptual => Color::from_oklab(&mix(&c0.to_oklab(), &c1.to_oklab(), local)),
+        }
+    }
+}
+
+fn mix(a: &Vec3, b: &Vec3, t: f32) -> Vec3 {
+    a + (b - a) * t
+}
@@ -0,0 +1,38 @@
+
+use nalgebra_glm::Vec3;
+
+#[derive(Debug, Clone, Copy)]
+pub enum LightKind {
+    Directional { direction: Vec3 },
+    Point { position: Vec3 },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Light {
+    pub kind: LightKind,
+    pub intensity: f32,
+}
+
+impl Light {
+    pub fn directional(direction: Vec3, intensity: f32) -> Self {
+        Light {
+            kind: LightKind::Directional { direction: direction.normalize() },
+            intensity,
+        }
+    }
+
+    pub fn point(position: Vec3, intensity: f32) -> Self {
+        Light {
+            kind: LightKind::Point { position },
+            intensity,
+        }
+    }
+
+    // Unit vector going from the surface point towards the light
+    pub fn direction_to(&self, point: &Vec3) -> Vec3 {
+        match self.kind {
+            LightKind::Directional { direction } => -direction,
+            LightKind::Point { position } => (position - point).normalize(),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+
+use crate::vertex::Vertex;
+use crate::obj::{Obj, Cluster};
+
+// One resolution of a mesh, drawn while the object is at least min_pixels tall on screen
+pub struct LodLevel {
+    pub vertices: Vec<Vertex>,
+    pub clusters: Vec<Cluster>,
+    // Farthest vertex of this level from the origin, what the object is culled with
+    pub radius: f32,
+    pub min_pixels: f32,
+}
+
+// The same shape at decreasing resolutions, finest first
+pub struct LodMesh {
+    pub levels: Vec<LodLevel>,
+    // Farthest vertex of the finest level from the origin
+    pub radius: f32,
+}
+
+impl LodMesh {
+    // Fraction of a threshold the size has to move past before the level changes, so an object
+    // sitting right at a threshold does not flicker between two levels
+    const HYSTERESIS: f32 = 0.15;
+    const TRIANGLES_PER_CLUSTER: usize = 32;
+
+    // Levels paired with the screen height they need, the last one is used at any size
+    pub fn new(levels: Vec<(Obj, f32)>) -> Self {
+        assert!(!levels.is_empty(), "a mesh needs at least one level");
+        let count = levels.len();
+        let levels: Vec<LodLevel> = levels.into_iter().enumerate().map(|(i, (obj, min_pixels))| {
+            let (vertices, clusters) = obj.get_clustered_vertex_array(LodMesh::TRIANGLES_PER_CLUSTER);
+            LodLevel { vertices, clusters, radius: obj.radius(), min_pixels: if i + 1 == count { 0.0 } else { min_pixels } }
+        }).collect();
+        let radius = levels[0].radius;
+        LodMesh { levels, radius }
+    }
+
+    // Loaded models get their coarser levels from vertex clustering, with the grid resolution
+    // of each level next to its threshold
+    pub fn simplified(obj: Obj, levels: &[(usize, f32)], finest_min_pixels: f32) -> Self {
+        let coarser: Vec<(Obj, f32)> = levels.iter()
+            .map(|&(cells, min_pixels)| (obj.simplified(cells), min_pixels))
+            .collect();
+        LodMesh::new(std::iter::once((obj, finest_min_pixels)).chain(coarser).collect())
+    }
+
+    pub fn level(&self, index: usize) -> &LodLevel {
+        &self.levels[index.min(self.levels.len() - 1)]
+    }
+
+    // Level for an object of the given screen height, starting from the one it had last frame
+    pub fn select(&self, pixels: f32, current: usize) -> usize {
+        let mut level = current.min(self.levels.len() - 1);
+        while level > 0 && pixels > self.levels[level - 1].min_pixels * (1.0 + LodMesh::HYSTERESIS) {
+            level -= 1;
+        }
+        while level + 1 < self.levels.len() && pixels < self.levels[level].min_pixels * (1.0 - LodMesh::HYSTERESIS) {
+            level += 1;
+        }
+        level
+    }
+}
@@ -1,5 +1,5 @@
-use nalgebra_glm::{Vec3, Mat4, look_at, perspective};
-use minifb::{Key, Window, WindowOptions};
+use nalgebra_glm::{Vec3, Vec4, Mat4, look_at, dot};
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
 use std::time::Duration;
 use std::f32::consts::PI;
 
@@ -11,32 +11,58 @@ mod color;
 mod fragment;
 mod shaders;
 mod camera;
+mod light;
+mod shadow;
+mod scene;
+mod texture;
+mod noise;
+mod gradient;
+mod bake;
+mod skybox;
+mod atmosphere;
+mod ring;
+mod orbit;
+mod clock;
+mod clipping;
+mod camera_path;
+mod frustum;
+mod mesh;
+mod lod;
 
 use framebuffer::Framebuffer;
 use vertex::Vertex;
 use obj::Obj;
-use camera::Camera;
+use camera::{Camera, CameraMode, ProjectionMode};
 use triangle::triangle;
-use shaders::{vertex_shader, fragment_shader};
-use fastnoise_lite::{FastNoiseLite, NoiseType, FractalType};
-
-pub struct Uniforms {
+use shaders::{vertex_shader, fragment_shader, Displacement, Shader, ShaderKind};
+use light::Light;
+use shadow::{ShadowMap, ShadowSettings, light_depth};
+use scene::{SceneObject, scene_bounds, animate, check_orbits};
+use texture::Texture;
+use noise::{NoiseConfig, Fractal};
+use gradient::Gradient;
+use bake::{TextureMapping, bake_equirectangular, bake_uv};
+use skybox::{Skybox, Starfield};
+use atmosphere::{Atmosphere, atmosphere_shader};
+use ring::{Ring, ring_shader};
+use orbit::{Orbit, Spin};
+use clock::Clock;
+use clipping::clip_triangle;
+use camera_path::CameraPath;
+use frustum::{Frustum, Visibility, CullStats};
+use mesh::Mesh;
+use lod::LodMesh;
+use fastnoise_lite::NoiseType;
+
+pub struct Uniforms<'a> {
     model_matrix: Mat4,
     view_matrix: Mat4,
     projection_matrix: Mat4,
     viewport_matrix: Mat4,
-    time: u32,
-    noise: FastNoiseLite
-}
-
-fn create_noise() -> FastNoiseLite {
-    create_cloud_noise()
-}
-
-fn create_cloud_noise() -> FastNoiseLite {
-    let mut noise = FastNoiseLite::with_seed(1337);
-    noise.set_noise_type(Some(NoiseType::OpenSimplex2));
-    noise
+    time: f32,
+    light: Light,
+    normal_map: Option<&'a Texture>,
+    displacement: Option<&'a Displacement>
 }
 
 fn create_model_matrix(translation: Vec3, scale: f32, rotation: Vec3) -> Mat4 {
@@ -82,15 +108,6 @@ fn create_view_matrix(eye: Vec3, center: Vec3, up: Vec3) -> Mat4 {
     look_at(&eye, &center, &up)
 }
 
-fn create_perspective_matrix(window_width: f32, window_height: f32) -> Mat4 {
-    let fov = 45.0 * PI / 180.0;
-    let aspect_ratio = window_width / window_height;
-    let near = 0.1;
-    let far = 1000.0;
-
-    perspective(fov, aspect_ratio, near, far)
-}
-
 fn create_viewport_matrix(width: f32, height: f32) -> Mat4 {
     Mat4::new(
         width / 2.0, 0.0, 0.0, width / 2.0,
@@ -100,7 +117,7 @@ fn create_viewport_matrix(width: f32, height: f32) -> Mat4 {
     )
 }
 
-fn render(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Vertex]) {
+fn render(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Vertex], shader: &Shader, light: &Light, shadow_map: &ShadowMap) {
     // Vertex Shader
     let mut transformed_vertices = Vec::with_capacity(vertex_array.len());
     for vertex in vertex_array {
@@ -110,29 +127,25 @@ fn render(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Ve
 
     // Primitive Assembly
     let mut triangles = Vec::new();
-    for i in (0..transformed_vertices.len()).step_by(3) {
-        if i + 2 < transformed_vertices.len() {
-            triangles.push([
-                transformed_vertices[i].clone(),
-                transformed_vertices[i + 1].clone(),
-                transformed_vertices[i + 2].clone(),
-            ]);
-        }
+    for tri in transformed_vertices.chunks_exact(3) {
+        triangles.extend(clip_triangle(tri, uniforms));
     }
 
     // Rasterization
     let mut fragments = Vec::new();
     for tri in &triangles {
-        fragments.extend(triangle(&tri[0], &tri[1], &tri[2]));
+        fragments.extend(triangle(&tri[0], &tri[1], &tri[2], light));
     }
 
     // Fragment Processing
-    for fragment in fragments {
+    for mut fragment in fragments {
         let x = fragment.position.x as usize;
         let y = fragment.position.y as usize;
 
         if x < framebuffer.width && y < framebuffer.height {
-            let shaded_color = fragment_shader(&fragment, &uniforms);
+            fragment.shadow = shadow_map.visibility(&fragment.world_position, &fragment.normal);
+            fragment.intensity *= fragment.shadow;
+            let shaded_color = fragment_shader(&fragment, uniforms, shader);
             let color = shaded_color.to_hex();
             framebuffer.set_current_color(color);
             framebuffer.point(x, y, fragment.depth);
@@ -140,7 +153,237 @@ fn render(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Ve
     }
 }
 
+// Transparent shell around a planet, drawn after the opaque geometry
+fn render_atmosphere(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Vertex], atmosphere: &Atmosphere, light: &Light) {
+    for tri in vertex_array.chunks_exact(3) {
+        let transformed: Vec<Vertex> = tri.iter().map(|vertex| vertex_shader(vertex, uniforms)).collect();
+
+        for [a, b, c] in clip_triangle(&transformed, uniforms) {
+            for fragment in triangle(&a, &b, &c, light) {
+                // Only the near half of the shell, the far half would double the glow
+                if dot(&fragment.view_normal, &fragment.view_position) > 0.0 {
+                    continue;
+                }
+                if fragment.position.x >= 0.0 && fragment.position.y >= 0.0 {
+                    let (color, alpha) = atmosphere_shader(&fragment, uniforms, atmosphere);
+                    framebuffer.blend_point(fragment.position.x as usize, fragment.position.y as usize, fragment.depth, color, alpha);
+                }
+            }
+        }
+    }
+}
+
+// Rings are transparent too, and pick up the planet shadow from the shadow map
+fn render_ring(framebuffer: &mut Framebuffer, uniforms: &Uniforms, ring: &Ring, light: &Light, shadow_map: &ShadowMap) {
+    for tri in ring.mesh.chunks_exact(3) {
+        let transformed: Vec<Vertex> = tri.iter().map(|vertex| vertex_shader(vertex, uniforms)).collect();
+
+        for [a, b, c] in clip_triangle(&transformed, uniforms) {
+            for mut fragment in triangle(&a, &b, &c, light) {
+                if fragment.position.x >= 0.0 && fragment.position.y >= 0.0 {
+                    // Both sides are lit, face the normal to the light for the shadow bias
+                    let to_light = light.direction_to(&fragment.world_position);
+                    let normal = if dot(&fragment.normal, &to_light) < 0.0 { -fragment.normal } else { fragment.normal };
+                    fragment.shadow = shadow_map.visibility(&fragment.world_position, &normal);
+
+                    let (color, alpha) = ring_shader(&fragment, uniforms, ring);
+                    framebuffer.blend_point(fragment.position.x as usize, fragment.position.y as usize, fragment.depth, color, alpha);
+                }
+            }
+        }
+    }
+}
+
+// Depth-only pass from the light point of view, storing light distances instead of screen depth
+fn render_depth(depth_buffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Vertex], light: &Light) {
+    let near = 0.05;
+    let light_view = uniforms.view_matrix * uniforms.model_matrix;
+
+    for tri in vertex_array.chunks_exact(3) {
+        let behind_light = tri.iter().any(|vertex| {
+            let view_position = light_view * Vec4::new(vertex.position.x, vertex.position.y, vertex.position.z, 1.0);
+            view_position.z > -near
+        });
+        if behind_light {
+            continue;
+        }
+
+        let transformed: Vec<Vertex> = tri.iter().map(|vertex| {
+            let mut transformed = vertex_shader(vertex, uniforms);
+            transformed.transformed_position.z = light_depth(light, &transformed.world_position);
+            transformed
+        }).collect();
+
+        for fragment in triangle(&transformed[0], &transformed[1], &transformed[2], light) {
+            if fragment.position.x >= 0.0 && fragment.position.y >= 0.0 {
+                depth_buffer.point_depth(fragment.position.x as usize, fragment.position.y as usize, fragment.depth);
+            }
+        }
+    }
+}
+
+// Objects cast shadows with the level of detail they are drawn with
+fn render_shadow_map(shadow_map: &mut ShadowMap, light: &Light, objects: &[SceneObject], mesh: &LodMesh, time: f32) {
+    let resolution = shadow_map.settings.resolution as f32;
+    let viewport_matrix = create_viewport_matrix(resolution, resolution);
+
+    for face in shadow_map.faces.iter_mut() {
+        face.depth.clear();
+
+        for object in objects {
+            let uniforms = Uniforms {
+                model_matrix: create_model_matrix(object.translation, object.scale, object.rotation),
+                view_matrix: face.view_matrix,
+                projection_matrix: face.projection_matrix,
+                viewport_matrix,
+                time,
+                light: *light,
+                normal_map: None,
+                displacement: object.displacement.as_ref()
+            };
+            render_depth(&mut face.depth, &uniforms, &mesh.level(object.lod).vertices, light);
+        }
+    }
+}
+
+// Number keys swap the shader of the planet
+const SHADER_KEYS: [(Key, ShaderKind); 7] = [
+    (Key::Key1, ShaderKind::Crater),
+    (Key::Key2, ShaderKind::BlackAndWhite),
+    (Key::Key3, ShaderKind::Dalmata),
+    (Key::Key4, ShaderKind::Cloud),
+    (Key::Key5, ShaderKind::Cellular),
+    (Key::Key6, ShaderKind::Lava),
+    (Key::Key7, ShaderKind::GasGiant),
+];
+
+// 8 and 9 load the planet textures written by --bake, see bake_from_args
+const BAKED_TEXTURES: [(Key, &str, TextureMapping); 2] = [
+    (Key::Key8, "assets/textures/baked.ppm", TextureMapping::Equirectangular),
+    (Key::Key9, "assets/textures/baked_uv.ppm", TextureMapping::Uv),
+];
+
+// --bake <shader> [--uv] [--size <width>x<height>] [--out <file>]
+// Evaluates a procedural shader once over the sphere and saves it as a PPM texture
+fn bake_from_args(args: &[String]) -> Result<(), String> {
+    let mut args = args.iter();
+    let name = args.next().ok_or("missing shader name after --bake")?;
+    let kind = ShaderKind::from_name(name).ok_or_else(|| format!("unknown shader '{}'", name))?;
+
+    let mut mapping = TextureMapping::Equirectangular;
+    let mut size = None;
+    let mut out = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--uv" => mapping = TextureMapping::Uv,
+            "--size" => {
+                let value = args.next().ok_or("missing value after --size")?;
+                let (width, height) = value.split_once('x').ok_or("size must look like 1024x512")?;
+                let parse = |n: &str| match n.parse::<usize>() {
+                    Ok(n) if n > 0 => Ok(n),
+                    _ => Err(format!("bad size '{}'", value)),
+                };
+                size = Some((parse(width)?, parse(height)?));
+            }
+            "--out" => out = Some(args.next().ok_or("missing file after --out")?.clone()),
+            other => return Err(format!("unknown argument '{}'", other)),
+        }
+    }
+
+    let default_out = BAKED_TEXTURES.iter().find(|(_, _, m)| *m == mapping).map(|(_, file, _)| file.to_string());
+    let out = out.or(default_out).ok_or("missing --out")?;
+    let (width, height) = size.unwrap_or(match mapping {
+        TextureMapping::Equirectangular => (1024, 512),
+        TextureMapping::Uv => (1024, 1024),
+    });
+
+    let obj = Obj::load("assets/models/sphere.obj").map_err(|e| e.to_string())?;
+    let vertex_array = obj.get_vertex_array();
+    let shader = Shader::new(kind);
+
+    let texture = match mapping {
+        TextureMapping::Equirectangular => {
+            let radius = vertex_array.iter().map(|v| v.position.magnitude()).fold(0.0, f32::max);
+            bake_equirectangular(&shader, radius, width, height, 0.0)
+        }
+        TextureMapping::Uv => bake_uv(&shader, &vertex_array, width, height, 0.0),
+    };
+
+    if let Some(dir) = std::path::Path::new(&out).parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    texture.save_ppm(&out).map_err(|e| format!("{}: {}", out, e))?;
+    println!("Baked {:?} to {} ({}x{})", kind, out, width, height);
+    Ok(())
+}
+
+// --headless <frames> [--out <dir>] [--camera-path <file>]
+// Renders without a window at a fixed 60 steps per second and saves every frame as a PPM,
+// optionally flying the camera along a recorded path
+struct Headless {
+    frames: usize,
+    out: String,
+    camera_path: Option<String>,
+}
+
+fn headless_from_args(args: &[String]) -> Result<Option<Headless>, String> {
+    let mut args = args.iter();
+    let mut headless = None;
+    let mut out = String::from("frames");
+    let mut camera_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--headless" => {
+                let frames = args.next().ok_or("missing frame count after --headless")?;
+                headless = match frames.parse::<usize>() {
+                    Ok(count) if count > 0 => Some(count),
+                    _ => return Err(format!("bad frame count '{}'", frames)),
+                };
+            }
+            "--out" => out = args.next().ok_or("missing directory after --out")?.clone(),
+            "--camera-path" => camera_path = Some(args.next().ok_or("missing file after --camera-path")?.clone()),
+            other => return Err(format!("unknown argument '{}'", other)),
+        }
+    }
+
+    Ok(headless.map(|frames| Headless { frames, out, camera_path }))
+}
+
+// Keyframes recorded with K, loaded on start so a path survives between runs
+const CAMERA_PATH_FILE: &str = "assets/camera_path.txt";
+
+fn create_light(point: bool) -> Light {
+    if point {
+        Light::point(Vec3::new(1.5, 1.0, 3.0), 1.0)
+    } else {
+        Light::directional(Vec3::new(-0.4, -0.2, -1.0), 1.0)
+    }
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("--bake") {
+        if let Err(error) = bake_from_args(&args[1..]) {
+            eprintln!("bake failed: {}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let headless = match headless_from_args(&args) {
+        Ok(headless) => headless,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    if let Some(headless) = &headless {
+        if let Err(error) = std::fs::create_dir_all(&headless.out) {
+            eprintln!("{}: {}", headless.out, error);
+            std::process::exit(1);
+        }
+    }
+
     let window_width = 800;
     let window_height = 600;
     let framebuffer_width = 800;
@@ -148,73 +391,486 @@ fn main() {
     let frame_delay = Duration::from_millis(16);
 
     let mut framebuffer = Framebuffer::new(framebuffer_width, framebuffer_height);
-    let mut window = Window::new(
-        "Animated Fragment Shader",
-        window_width,
-        window_height,
-        WindowOptions::default(),
-    )
-    .unwrap();
-
-    window.set_position(500, 500);
-    window.update();
+    let mut window = match headless {
+        Some(_) => None,
+        None => Some(Window::new(
+            "Animated Fragment Shader",
+            window_width,
+            window_height,
+            WindowOptions::default(),
+        )
+        .unwrap()),
+    };
+
+    if let Some(window) = window.as_mut() {
+        window.set_position(500, 500);
+        window.update();
+    }
 
     framebuffer.set_background_color(0x333355);
 
-    // model position
-    let translation = Vec3::new(0.0, 0.0, 0.0);
-    let rotation = Vec3::new(0.0, 0.0, 0.0);
-    let scale = 1.0f32;
+    // Mars and its moons Fobos and Deimos, each shader builds its noise once here.
+    // The moons are tidally locked, they turn once per orbit.
+    let mut objects = vec![
+        SceneObject::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0), 1.0)
+            .with_shader(Shader::new(ShaderKind::Crater))
+            .with_atmosphere(ShaderKind::Crater.default_atmosphere().expect("crater preset has an atmosphere"))
+            .with_displacement(Displacement::mountains(0.04, 250.0).with_fractal(Fractal::new(5, 2.1, 0.5)))
+            .with_spin(Spin::new(25.2_f32.to_radians(), 40.0)),
+        SceneObject::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0), 0.25)
+            .with_shader(Shader::with_noise(ShaderKind::Crater, &[NoiseConfig::new(NoiseType::OpenSimplex2, 7)]))
+            .with_displacement(Displacement::lava_bulges(0.08, 150.0, 30.0)
+                .with_noise(&NoiseConfig::new(NoiseType::OpenSimplex2, 7)))
+            .with_orbit(Orbit::circular(0, 1.2, 12.0).with_phase(0.8).with_plane(0.15, 0.4))
+            .with_spin(Spin::new(0.0, 12.0)),
+        SceneObject::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0), 0.12)
+            .with_shader(Shader::with_noise(ShaderKind::Crater, &[NoiseConfig::new(NoiseType::OpenSimplex2, 42)])
+                .with_palette(Gradient::from_hex(&["#4a4038", "#7c7068", "#b4aaa0"]).expect("valid palette")))
+            .with_orbit(Orbit::elliptical(0, 1.75, 0.15, 30.0).with_phase(0.55).with_plane(0.3, -0.5).with_periapsis(1.0))
+            .with_spin(Spin::new(0.0, 30.0)),
+    ];
+    if let Err(error) = check_orbits(&objects) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+    // M cycles the shape every object is drawn with, the sphere model first. Every shape comes
+    // in three resolutions, the finest for objects over 150 pixels tall and the coarsest under 50.
+    let lod_thresholds = [150.0, 50.0, 0.0];
+    let generated = |levels: [Mesh; 3]| LodMesh::new(levels.map(Obj::from_mesh).into_iter().zip(lod_thresholds).collect());
+    let sphere = Obj::load("assets/models/sphere.obj").expect("Failed to load obj");
+    let meshes = [
+        LodMesh::simplified(sphere, &[(8, lod_thresholds[1]), (4, lod_thresholds[2])], lod_thresholds[0]),
+        generated([Mesh::uv_sphere(0.5, 48, 24), Mesh::uv_sphere(0.5, 24, 12), Mesh::uv_sphere(0.5, 12, 6)]),
+        generated([Mesh::icosphere(0.5, 3), Mesh::icosphere(0.5, 2), Mesh::icosphere(0.5, 1)]),
+        generated([Mesh::torus(0.38, 0.14, 48, 24), Mesh::torus(0.38, 0.14, 24, 12), Mesh::torus(0.38, 0.14, 12, 6)]),
+        generated([Mesh::cube(0.75, 8), Mesh::cube(0.75, 4), Mesh::cube(0.75, 1)]),
+        generated([Mesh::cylinder(0.4, 0.8, 48), Mesh::cylinder(0.4, 0.8, 24), Mesh::cylinder(0.4, 0.8, 12)]),
+        generated([Mesh::plane(1.0, 16), Mesh::plane(1.0, 8), Mesh::plane(1.0, 2)]),
+    ];
+    let mut mesh_index = 0;
+    // The shadow map covers the scene with the largest of them, so switching needs no rebuild
+    let largest_mesh_radius = meshes.iter().map(|mesh| mesh.radius).fold(0.0, f32::max);
+    let (mut scene_center, mut scene_radius) = scene_bounds(&objects, largest_mesh_radius);
+
+    // light and shadows, L switches between a directional and a point light
+    let mut point_light = false;
+    let mut light = create_light(point_light);
+    let mut shadow_settings = ShadowSettings::default();
+    let mut shadow_map = ShadowMap::new(&light, shadow_settings, scene_center, scene_radius);
 
     // camera parameters
+    let mut mouse = MouseControls::default();
     let mut camera = Camera::new(
         Vec3::new(0.0, 0.0, 5.0),
         Vec3::new(0.0, 0.0, 0.0),
         Vec3::new(0.0, 1.0, 0.0)
     );
 
-    let obj = Obj::load("assets/models/sphere.obj").expect("Failed to load obj");
-    let vertex_arrays = obj.get_vertex_array(); 
-    let mut time = 0;
-
-    while window.is_open() {
-        if window.is_key_down(Key::Escape) {
-            break;
+    let mut cull_stats = CullStats::default();
+    // optional tangent space normal map, surfaces fall back to procedural bumps without it
+    let normal_map = Texture::load_ppm("assets/textures/normal_map.ppm").ok();
+
+    // backgrounds cycled with B, an optional equirectangular sky image comes first. T makes the
+    // stars twinkle.
+    let mut skyboxes: Vec<Skybox> = Texture::load_ppm("assets/textures/sky.ppm").ok()
+        .map(Skybox::Equirectangular)
+        .into_iter()
+        .collect();
+    skyboxes.push(Skybox::Starfield(Starfield::new(7, 3000)));
+    skyboxes.push(Skybox::Flat);
+    let mut skybox_index = 0;
+
+    // P pauses, period steps one frame while paused, [ and ] slow down and speed up time
+    let mut clock = match headless {
+        Some(_) => Clock::fixed_step(1.0 / 60.0),
+        None => Clock::real_time(),
+    };
+
+    // K records the current view as a keyframe, backspace clears the path and enter plays it,
+    // playback holds the clock time it started at
+    let (mut camera_path, mut playback) = match headless.as_ref().and_then(|h| h.camera_path.as_ref()) {
+        Some(file) => match CameraPath::load(file) {
+            Ok(path) => (path, Some(0.0)),
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        },
+        None => match CameraPath::load(CAMERA_PATH_FILE) {
+            Ok(path) => (path, None),
+            Err(error) => {
+                if error.kind() != std::io::ErrorKind::NotFound {
+                    eprintln!("{}", error);
+                }
+                (CameraPath::new(), None)
+            }
+        },
+    };
+    let mut focused: Option<usize> = None;
+    let mut frame = 0;
+    let mut scene_changed = true;
+
+    loop {
+        if let Some(window) = window.as_ref() {
+            if !window.is_open() || window.is_key_down(Key::Escape) {
+                break;
+            }
+
+            handle_input(window, &mut camera, clock.frame_time());
+            handle_mouse(window, &mut camera, &mut mouse);
+
+            if window.is_key_pressed(Key::P, KeyRepeat::No) {
+                clock.toggle_pause();
+            }
+            if window.is_key_pressed(Key::Period, KeyRepeat::Yes) {
+                clock.step();
+            }
+            if window.is_key_pressed(Key::LeftBracket, KeyRepeat::No) {
+                clock.scale_time(0.5);
+            }
+            if window.is_key_pressed(Key::RightBracket, KeyRepeat::No) {
+                clock.scale_time(2.0);
+            }
+
+            if window.is_key_pressed(Key::L, KeyRepeat::No) {
+                point_light = !point_light;
+                light = create_light(point_light);
+                // point lights render six cube faces, keep them cheaper
+                shadow_settings.resolution = if point_light { 512 } else { 1024 };
+                shadow_map = ShadowMap::new(&light, shadow_settings, scene_center, scene_radius);
+                scene_changed = true;
+            }
+
+            for (key, kind) in SHADER_KEYS {
+                if window.is_key_pressed(key, KeyRepeat::No) {
+                    objects[0].shader = Shader::new(kind);
+                    objects[0].atmosphere = kind.default_atmosphere();
+                    objects[0].rings = kind.default_rings();
+                    // rings change how much of the scene the shadow map has to cover
+                    (scene_center, scene_radius) = scene_bounds(&objects, largest_mesh_radius);
+                    shadow_map = ShadowMap::new(&light, shadow_settings, scene_center, scene_radius);
+                    scene_changed = true;
+                }
+            }
+
+            for (key, file, mapping) in BAKED_TEXTURES {
+                if window.is_key_pressed(key, KeyRepeat::No) {
+                    match Texture::load_ppm(file) {
+                        Ok(texture) => {
+                            objects[0].shader = Shader::textured(texture, mapping);
+                            scene_changed = true;
+                        }
+                        Err(error) => eprintln!("{} (create it with --bake)", error),
+                    }
+                }
+            }
+
+            if window.is_key_pressed(Key::K, KeyRepeat::No) {
+                camera_path.record(&camera);
+                if let Err(error) = camera_path.save(CAMERA_PATH_FILE) {
+                    eprintln!("{}: {}", CAMERA_PATH_FILE, error);
+                }
+            }
+            if window.is_key_pressed(Key::Backspace, KeyRepeat::No) {
+                camera_path = CameraPath::new();
+                playback = None;
+                // Otherwise the cleared path comes back on the next start
+                if let Err(error) = std::fs::remove_file(CAMERA_PATH_FILE) {
+                    if error.kind() != std::io::ErrorKind::NotFound {
+                        eprintln!("{}: {}", CAMERA_PATH_FILE, error);
+                    }
+                }
+            }
+            if window.is_key_pressed(Key::Enter, KeyRepeat::No) {
+                playback = match playback {
+                    Some(_) => None,
+                    None if camera_path.keyframes.len() > 1 => {
+                        camera.unfocus();
+                        Some(clock.elapsed())
+                    }
+                    None => None,
+                };
+            }
+
+            // Tab frames the next object, shift + tab the previous one
+            if window.is_key_pressed(Key::Tab, KeyRepeat::No) {
+                let count = objects.len();
+                let backwards = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+                let index = match (focused, backwards) {
+                    (None, false) => 0,
+                    (None, true) => count - 1,
+                    (Some(i), false) => (i + 1) % count,
+                    (Some(i), true) => (i + count - 1) % count,
+                };
+                let (center, radius) = objects[index].bounding_sphere(meshes[mesh_index].radius);
+                camera.focus(center, radius, framebuffer_width as f32 / framebuffer_height as f32);
+                focused = Some(index);
+            }
+
+            if window.is_key_pressed(Key::M, KeyRepeat::No) {
+                mesh_index = (mesh_index + 1) % meshes.len();
+                scene_changed = true;
+            }
+
+            if window.is_key_pressed(Key::B, KeyRepeat::No) {
+                skybox_index = (skybox_index + 1) % skyboxes.len();
+                scene_changed = true;
+            }
+            if window.is_key_pressed(Key::T, KeyRepeat::No) {
+                for skybox in skyboxes.iter_mut() {
+                    if let Skybox::Starfield(starfield) = skybox {
+                        starfield.twinkle = if starfield.twinkle > 0.0 { 0.0 } else { 0.5 };
+                    }
+                }
+                scene_changed = true;
+            }
         }
 
-        time += 1;
+        clock.tick();
+        let time = clock.elapsed();
 
-        handle_input(&window, &mut camera);
+        animate(&mut objects, time);
+
+        if let Some(index) = focused {
+            camera.follow(objects[index].translation);
+        }
+        camera.update(clock.frame_time());
+        // Panning or flying away drops the focus
+        if !camera.has_focus() {
+            focused = None;
+        }
 
-        framebuffer.clear();
+        // Playback follows the clock, so pausing or scaling time affects the flythrough too
+        if let Some(start) = playback {
+            if let Some(keyframe) = camera_path.sample(time - start) {
+                keyframe.apply(&mut camera);
+            }
+            if time - start > camera_path.duration() && window.is_some() {
+                playback = None;
+            }
+        }
 
-        let noise = create_noise();
-        let model_matrix = create_model_matrix(translation, scale, rotation);
-        let view_matrix = create_view_matrix(camera.eye, camera.center, camera.up);
-        let projection_matrix = create_perspective_matrix(window_width as f32, window_height as f32);
-        let viewport_matrix = create_viewport_matrix(framebuffer_width as f32, framebuffer_height as f32);
-        let uniforms = Uniforms { 
-            model_matrix, 
-            view_matrix, 
-            projection_matrix, 
-            viewport_matrix,
-            time,
-            noise
-        };
+        // Static frames present the previous buffer again instead of rendering from scratch. That
+        // only happens once nothing moves: the moons orbit and spin, so the default scene idles
+        // while paused or with a static shader on objects without spin or orbit.
+        let camera_changed = camera.check_if_changed();
+        let animated = clock.delta() > 0.0
+            && (objects.iter().any(SceneObject::is_animated) || skyboxes[skybox_index].is_animated());
+        let idle = !(camera_changed || scene_changed || animated);
+        if !idle {
+            scene_changed = false;
+
+            let mesh = &meshes[mesh_index];
+            for object in objects.iter_mut() {
+                // The body alone, rings and atmospheres do not depend on the mesh resolution
+                let pixels = camera.projected_size(&object.translation, object.scale * mesh.radius, framebuffer_height as f32);
+                object.lod = mesh.select(pixels, object.lod);
+            }
+
+            render_shadow_map(&mut shadow_map, &light, &objects, mesh, time);
+
+            framebuffer.clear();
+
+            let view_matrix = create_view_matrix(camera.eye, camera.center, camera.up);
+            let aspect_ratio = framebuffer_width as f32 / framebuffer_height as f32;
+            let projection_matrix = camera.projection_matrix(aspect_ratio);
+            let viewport_matrix = create_viewport_matrix(framebuffer_width as f32, framebuffer_height as f32);
+
+            // The sky stays a perspective backdrop in orthographic mode, where every pixel
+            // would otherwise look in the same direction
+            let sky_projection = camera.projection.perspective_matrix(aspect_ratio);
+            skyboxes[skybox_index].render(&mut framebuffer, &camera, &view_matrix, &sky_projection, &viewport_matrix, time);
+
+            // Shadows still come from every object, only the camera passes are culled
+            let frustum = Frustum::from_matrix(&(projection_matrix * view_matrix));
+            let visible: Vec<Visibility> = objects.iter()
+                .map(|object| {
+                    let (center, radius) = object.bounding_sphere(mesh.level(object.lod).radius);
+                    frustum.test_sphere(&center, radius)
+                })
+                .collect();
+            cull_stats = CullStats::default();
+
+            framebuffer.set_current_color(0xFFDDDD);
+            for (object, &visibility) in objects.iter().zip(&visible) {
+                if visibility == Visibility::Outside {
+                    cull_stats.objects_culled += 1;
+                    continue;
+                }
+                cull_stats.objects_drawn += 1;
+
+                let uniforms = Uniforms {
+                    model_matrix: create_model_matrix(object.translation, object.scale, object.rotation),
+                    view_matrix,
+                    projection_matrix,
+                    viewport_matrix,
+                    time,
+                    light,
+                    normal_map: normal_map.as_ref(),
+                    displacement: object.displacement.as_ref()
+                };
+
+                let level = mesh.level(object.lod);
+                if visibility == Visibility::Inside {
+                    render(&mut framebuffer, &uniforms, &level.vertices, &object.shader, &light, &shadow_map);
+                    cull_stats.clusters_drawn += level.clusters.len();
+                    cull_stats.triangles += level.vertices.len() / 3;
+                    continue;
+                }
+                // Displacement pushes vertices out along the normal by at most the amplitude
+                let grow = object.displacement.as_ref().map_or(0.0, |d| d.amplitude);
+                for cluster in &level.clusters {
+                    let bounds = cluster.bounds.grow(grow).transform(&uniforms.model_matrix);
+                    if frustum.test_aabb(&bounds) == Visibility::Outside {
+                        cull_stats.clusters_culled += 1;
+                        continue;
+                    }
+                    cull_stats.clusters_drawn += 1;
+                    cull_stats.triangles += cluster.vertices.len() / 3;
+                    render(&mut framebuffer, &uniforms, &level.vertices[cluster.vertices.clone()], &object.shader, &light, &shadow_map);
+                }
+            }
+
+            for (object, &visibility) in objects.iter().zip(&visible) {
+                if visibility == Visibility::Outside {
+                    continue;
+                }
+                if let Some(ring) = &object.rings {
+                    let planet_matrix = create_model_matrix(object.translation, object.scale, object.rotation);
+                    let uniforms = Uniforms {
+                        model_matrix: planet_matrix * create_model_matrix(Vec3::zeros(), 1.0, ring.tilt),
+                        view_matrix,
+                        projection_matrix,
+                        viewport_matrix,
+                        time,
+                        light,
+                        normal_map: None,
+                        displacement: None
+                    };
+                    render_ring(&mut framebuffer, &uniforms, ring, &light, &shadow_map);
+                }
+            }
+
+            for (object, &visibility) in objects.iter().zip(&visible) {
+                if visibility == Visibility::Outside {
+                    continue;
+                }
+                if let Some(atmosphere) = &object.atmosphere {
+                    let uniforms = Uniforms {
+                        model_matrix: create_model_matrix(object.translation, object.scale * (1.0 + atmosphere.thickness), object.rotation),
+                        view_matrix,
+                        projection_matrix,
+                        viewport_matrix,
+                        time,
+                        light,
+                        normal_map: None,
+                        displacement: None
+                    };
+                    render_atmosphere(&mut framebuffer, &uniforms, &mesh.level(object.lod).vertices, atmosphere, &light);
+                }
+            }
+        }
 
+        match (window.as_mut(), &headless) {
+            (Some(window), _) => {
+                let paused = if clock.paused { " (paused)" } else { "" };
+                let idle = if idle { " - idle" } else { "" };
+                let projection = match camera.projection.mode {
+                    ProjectionMode::Perspective => format!("{:.0}° fov", camera.projection.fov.to_degrees()),
+                    ProjectionMode::Orthographic => String::from("orthographic"),
+                };
+                let mode = match (camera.mode, camera.free_rotation) {
+                    (CameraMode::Orbit, false) => "orbit",
+                    (CameraMode::Orbit, true) => "arcball",
+                    (CameraMode::Fly, false) => "fly",
+                    (CameraMode::Fly, true) => "free fly",
+                };
+                let clip = format!("{:.3}..{:.0} clip", camera.projection.near, camera.projection.far);
+                let culled = format!(
+                    "{}/{} objects, {}/{} clusters culled, {} triangles",
+                    cull_stats.objects_culled, cull_stats.objects_culled + cull_stats.objects_drawn,
+                    cull_stats.clusters_culled, cull_stats.clusters_culled + cull_stats.clusters_drawn,
+                    cull_stats.triangles,
+                );
+                window.set_title(&format!("Animated Fragment Shader - {:.1}s (+{:.1}ms) x{}{} - {}, {}, {} - {}{}", time, clock.delta() * 1000.0, clock.time_scale, paused, mode, projection, clip, culled, idle));
+                window
+                    .update_with_buffer(&framebuffer.buffer, framebuffer_width, framebuffer_height)
+                    .unwrap();
+
+                std::thread::sleep(frame_delay);
+            }
+            (None, Some(headless)) => {
+                let file = format!("{}/frame_{:04}.ppm", headless.out, frame);
+                if let Err(error) = framebuffer.save_ppm(&file) {
+                    eprintln!("{}: {}", file, error);
+                    std::process::exit(1);
+                }
+                frame += 1;
+                if frame >= headless.frames {
+                    break;
+                }
+            }
+            (None, None) => unreachable!("there is always a window outside headless mode"),
+        }
+    }
+}
 
-        framebuffer.set_current_color(0xFFDDDD);
-        render(&mut framebuffer, &uniforms, &vertex_arrays);
+// F switches between orbiting the center and flying, delta is the real frame time in seconds
+fn handle_input(window: &Window, camera: &mut Camera, delta: f32) {
+    let fov_speed = PI / 180.0;
+    let clip_speed = 1.05;
 
-        window
-            .update_with_buffer(&framebuffer.buffer, framebuffer_width, framebuffer_height)
-            .unwrap();
+    if window.is_key_pressed(Key::F, KeyRepeat::No) {
+      camera.toggle_mode();
+    }
 
-        std::thread::sleep(frame_delay);
+    // R switches to free rotation, Z and X roll and U turns the camera back upright
+    let roll_speed = 1.5;
+    if window.is_key_pressed(Key::R, KeyRepeat::No) {
+      camera.toggle_free_rotation();
+    }
+    if camera.free_rotation {
+      if window.is_key_down(Key::Z) {
+        camera.roll(-roll_speed * delta);
+      }
+      if window.is_key_down(Key::X) {
+        camera.roll(roll_speed * delta);
+      }
+    }
+    if window.is_key_pressed(Key::U, KeyRepeat::No) {
+      camera.level();
+    }
+    match camera.mode {
+      CameraMode::Orbit => handle_orbit_input(window, camera),
+      CameraMode::Fly => handle_fly_input(window, camera, delta),
+    }
+
+    // Projection controls, - and = or shift and the mouse wheel change the field of view
+    if window.is_key_down(Key::Minus) {
+      camera.change_fov(-fov_speed);
+    }
+    if window.is_key_down(Key::Equal) {
+      camera.change_fov(fov_speed);
+    }
+    if window.is_key_pressed(Key::O, KeyRepeat::No) {
+      camera.toggle_orthographic();
+    }
+    // Home and End move the near plane, page up and page down the far plane
+    if window.is_key_down(Key::Home) {
+      camera.scale_near(1.0 / clip_speed);
+    }
+    if window.is_key_down(Key::End) {
+      camera.scale_near(clip_speed);
+    }
+    if window.is_key_down(Key::PageDown) {
+      camera.scale_far(1.0 / clip_speed);
+    }
+    if window.is_key_down(Key::PageUp) {
+      camera.scale_far(clip_speed);
     }
 }
 
-fn handle_input(window: &Window, camera: &mut Camera) {
+fn handle_orbit_input(window: &Window, camera: &mut Camera) {
     let movement_speed = 1.0;
     let rotation_speed = PI/50.0;
     let zoom_speed = 0.1;
@@ -259,3 +915,109 @@ fn handle_input(window: &Window, camera: &mut Camera) {
       camera.zoom(-zoom_speed);
     }
 }
+
+// W and S move forward and back, A and D strafe, E and Q go up and down, the arrows look around
+// and shift speeds everything up
+fn handle_fly_input(window: &Window, camera: &mut Camera, delta: f32) {
+    let movement_speed = 2.0;
+    let look_speed = 1.5;
+    let boost = if window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift) { 5.0 } else { 1.0 };
+
+    let mut movement = Vec3::new(0.0, 0.0, 0.0);
+    for (key, direction) in [
+        (Key::D, Vec3::new(1.0, 0.0, 0.0)),
+        (Key::A, Vec3::new(-1.0, 0.0, 0.0)),
+        (Key::E, Vec3::new(0.0, 1.0, 0.0)),
+        (Key::Q, Vec3::new(0.0, -1.0, 0.0)),
+        (Key::W, Vec3::new(0.0, 0.0, 1.0)),
+        (Key::S, Vec3::new(0.0, 0.0, -1.0)),
+    ] {
+        if window.is_key_down(key) {
+            movement += direction;
+        }
+    }
+    if movement.magnitude() > 0.0 {
+      camera.fly(movement.normalize() * movement_speed * boost * delta);
+    }
+
+    let mut yaw = 0.0;
+    let mut pitch = 0.0;
+    if window.is_key_down(Key::Left) {
+      yaw -= look_speed;
+    }
+    if window.is_key_down(Key::Right) {
+      yaw += look_speed;
+    }
+    if window.is_key_down(Key::Up) {
+      pitch += look_speed;
+    }
+    if window.is_key_down(Key::Down) {
+      pitch -= look_speed;
+    }
+    if yaw != 0.0 || pitch != 0.0 {
+      camera.look(yaw * delta, pitch * delta);
+    }
+}
+
+// Mouse sensitivities, plus the cursor position of the last frame so drags move the camera by
+// how far the cursor went since then
+struct MouseControls {
+    // Radians per pixel
+    orbit_sensitivity: f32,
+    // 1.0 keeps the point under the cursor at the camera center distance
+    pan_sensitivity: f32,
+    // Fraction of the distance to the center per wheel notch
+    zoom_sensitivity: f32,
+    last_position: Option<(f32, f32)>,
+}
+
+impl Default for MouseControls {
+    fn default() -> Self {
+        MouseControls {
+            orbit_sensitivity: 0.01,
+            pan_sensitivity: 1.0,
+            zoom_sensitivity: 0.1,
+            last_position: None,
+        }
+    }
+}
+
+// Left drag orbits, or looks around in fly mode, middle or shift + left drag pans, the wheel
+// zooms, or moves forward in fly mode, and shift + wheel changes the field of view
+fn handle_mouse(window: &Window, camera: &mut Camera, mouse: &mut MouseControls) {
+    let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+    let position = window.get_mouse_pos(MouseMode::Discard);
+    let left = window.get_mouse_down(MouseButton::Left);
+    let middle = window.get_mouse_down(MouseButton::Middle);
+
+    if let (Some((x, y)), Some((last_x, last_y))) = (position, mouse.last_position) {
+        let (dx, dy) = (x - last_x, y - last_y);
+        if dx != 0.0 || dy != 0.0 {
+            if middle || (left && shift) {
+                // World units covered by one pixel at the center distance
+                let (_, height) = window.get_size();
+                let pixel = 2.0 * camera.distance() * (camera.projection.fov * 0.5).tan() / height.max(1) as f32;
+                let scale = pixel * mouse.pan_sensitivity;
+                camera.pan(-dx * scale, dy * scale);
+            } else if left {
+                match camera.mode {
+                    CameraMode::Orbit => camera.orbit(dx * mouse.orbit_sensitivity, -dy * mouse.orbit_sensitivity),
+                    CameraMode::Fly => camera.look(dx * mouse.orbit_sensitivity, -dy * mouse.orbit_sensitivity),
+                }
+            }
+        }
+    }
+    mouse.last_position = position;
+
+    if let Some((_, scroll)) = window.get_scroll_wheel() {
+        if shift {
+            camera.change_fov(-scroll.signum() * PI / 90.0);
+        } else {
+            let notches = scroll.clamp(-3.0, 3.0);
+            match camera.mode {
+                CameraMode::Orbit => camera.zoom(camera.distance() * notches * mouse.zoom_sensitivity),
+                CameraMode::Fly => camera.fly(Vec3::new(0.0, 0.0, notches * mouse.zoom_sensitivity)),
+            }
+        }
+    }
+}
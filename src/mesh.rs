@@ -0,0 +1,232 @@
+
+use nalgebra_glm::{Vec2, Vec3};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use crate::vertex::Vertex;
+use crate::obj::compute_tangents;
+use crate::noise::{lat_long, lat_long_to_direction};
+
+// Indexed triangle mesh with per-vertex normals and UVs, counter-clockwise seen from the side the
+// normals point to. Every generator is centered on the origin.
+pub struct Mesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub texcoords: Vec<Vec2>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    fn empty() -> Self {
+        Mesh { positions: Vec::new(), normals: Vec::new(), texcoords: Vec::new(), indices: Vec::new() }
+    }
+
+    fn push_vertex(&mut self, position: Vec3, normal: Vec3, tex_coords: Vec2) -> u32 {
+        self.positions.push(position);
+        self.normals.push(normal);
+        self.texcoords.push(tex_coords);
+        (self.positions.len() - 1) as u32
+    }
+
+    // Grid of columns x rows quads over (s, t) in 0..1. The surface function gives the position,
+    // normal and texture coordinates, and has to turn s x t towards the normal. Triangles that
+    // collapse, like the ones touching a pole, are left out.
+    fn parametric(&mut self, columns: usize, rows: usize, surface: impl Fn(f32, f32) -> (Vec3, Vec3, Vec2)) {
+        let start = self.positions.len() as u32;
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let (position, normal, tex_coords) = surface(column as f32 / columns as f32, row as f32 / rows as f32);
+                self.push_vertex(position, normal, tex_coords);
+            }
+        }
+
+        let index = |column: usize, row: usize| start + (row * (columns + 1) + column) as u32;
+        for row in 0..rows {
+            for column in 0..columns {
+                let (a, b) = (index(column, row), index(column + 1, row));
+                let (c, d) = (index(column, row + 1), index(column + 1, row + 1));
+                self.push_triangle(a, b, c);
+                self.push_triangle(b, d, c);
+            }
+        }
+    }
+
+    fn push_triangle(&mut self, a: u32, b: u32, c: u32) {
+        let (pa, pb, pc) = (self.positions[a as usize], self.positions[b as usize], self.positions[c as usize]);
+        if (pb - pa).cross(&(pc - pa)).magnitude() > 1e-10 {
+            self.indices.extend([a, b, c]);
+        }
+    }
+
+    // Longitude and latitude grid, with the same UVs as the equirectangular mapping
+    pub fn uv_sphere(radius: f32, segments: usize, rings: usize) -> Self {
+        let mut mesh = Mesh::empty();
+        mesh.parametric(segments.max(3), rings.max(2), |u, v| {
+            let uv = Vec2::new(u, v);
+            let normal = lat_long_to_direction(&uv);
+            (normal * radius, normal, uv)
+        });
+        mesh
+    }
+
+    // Icosahedron with every face split in four per subdivision, evenly spread vertices without
+    // the crowding of the UV sphere at the poles
+    pub fn icosphere(radius: f32, subdivisions: usize) -> Self {
+        let t = (1.0 + 5.0_f32.sqrt()) / 2.0;
+        let mut directions: Vec<Vec3> = [
+            (-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+            (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+            (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0),
+        ].iter().map(|&(x, y, z)| Vec3::new(x, y, z).normalize()).collect();
+
+        let mut faces: Vec<[u32; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+
+        for _ in 0..subdivisions {
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut midpoint = |a: u32, b: u32| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    directions.push((directions[a as usize] + directions[b as usize]).normalize());
+                    (directions.len() - 1) as u32
+                })
+            };
+            faces = faces.iter().flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            }).collect();
+        }
+
+        let mut mesh = Mesh::empty();
+        for direction in &directions {
+            mesh.push_vertex(direction * radius, *direction, lat_long(direction));
+        }
+
+        for face in faces {
+            let [a, b, c] = face.map(|i| i as usize);
+            let (da, db, dc) = (directions[a], directions[b], directions[c]);
+            let face = if (db - da).cross(&(dc - da)).dot(&da) < 0.0 { [face[0], face[2], face[1]] } else { face };
+
+            // Faces across the longitude seam get copies of their vertices with u past 1. The
+            // poles, which appear from the first subdivision on, have no longitude of their own
+            // and take the average of the other two corners on every face.
+            let mut uvs = face.map(|i| mesh.texcoords[i as usize]);
+            let pole = face.map(|i| directions[i as usize].y.abs() > 1.0 - 1e-6);
+            let longitudes = || (0..3).filter(|&k| !pole[k]).map(|k| uvs[k].x);
+            if longitudes().fold(0.0, f32::max) - longitudes().fold(1.0, f32::min) > 0.5 {
+                for (uv, _) in uvs.iter_mut().zip(pole).filter(|(uv, pole)| !pole && uv.x < 0.5) {
+                    uv.x += 1.0;
+                }
+            }
+            if let Some(k) = (0..3).find(|&k| pole[k]) {
+                uvs[k].x = (0..3).filter(|&other| other != k).map(|other| uvs[other].x).sum::<f32>() / 2.0;
+            }
+
+            let corners = face.map(|i| i as usize);
+            let indices: Vec<u32> = (0..3).map(|k| {
+                let i = corners[k];
+                if mesh.texcoords[i] == uvs[k] {
+                    i as u32
+                } else {
+                    let (position, normal) = (mesh.positions[i], mesh.normals[i]);
+                    mesh.push_vertex(position, normal, uvs[k])
+                }
+            }).collect();
+            mesh.push_triangle(indices[0], indices[1], indices[2]);
+        }
+
+        mesh
+    }
+
+    // Ring around the Y axis, u goes around the main circle and v around the tube
+    pub fn torus(major_radius: f32, minor_radius: f32, segments: usize, sides: usize) -> Self {
+        let mut mesh = Mesh::empty();
+        mesh.parametric(segments.max(3), sides.max(3), |u, v| {
+            let theta = u * 2.0 * PI;
+            // Decreasing so s x t points out of the tube
+            let phi = -v * 2.0 * PI;
+            let normal = Vec3::new(phi.cos() * theta.cos(), phi.sin(), phi.cos() * theta.sin());
+            let center = Vec3::new(theta.cos(), 0.0, theta.sin()) * major_radius;
+            (center + normal * minor_radius, normal, Vec2::new(u, v))
+        });
+        mesh
+    }
+
+    // Flat square grid on the XZ plane facing +Y
+    pub fn plane(size: f32, subdivisions: usize) -> Self {
+        let mut mesh = Mesh::empty();
+        mesh.parametric(subdivisions.max(1), subdivisions.max(1), |s, t| {
+            let position = Vec3::new(s - 0.5, 0.0, 0.5 - t) * size;
+            (position, Vec3::new(0.0, 1.0, 0.0), Vec2::new(s, 1.0 - t))
+        });
+        mesh
+    }
+
+    // Six subdivided faces with their own vertices, so the edges stay sharp
+    pub fn cube(size: f32, subdivisions: usize) -> Self {
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let z = Vec3::new(0.0, 0.0, 1.0);
+        // Normal, then right and up as seen from outside the face
+        let faces = [(x, -z, y), (-x, z, y), (y, x, -z), (-y, x, z), (z, x, y), (-z, -x, y)];
+
+        let mut mesh = Mesh::empty();
+        for (normal, right, up) in faces {
+            mesh.parametric(subdivisions.max(1), subdivisions.max(1), |s, t| {
+                let position = (normal * 0.5 + right * (s - 0.5) + up * (t - 0.5)) * size;
+                (position, normal, Vec2::new(s, 1.0 - t))
+            });
+        }
+        mesh
+    }
+
+    // Closed cylinder along the Y axis, the caps are mapped from above
+    pub fn cylinder(radius: f32, height: f32, segments: usize) -> Self {
+        let segments = segments.max(3);
+        let mut mesh = Mesh::empty();
+        mesh.parametric(segments, 1, |u, v| {
+            let angle = u * 2.0 * PI;
+            let normal = Vec3::new(angle.cos(), 0.0, angle.sin());
+            let position = normal * radius + Vec3::new(0.0, (0.5 - v) * height, 0.0);
+            (position, normal, Vec2::new(u, v))
+        });
+
+        for side in [1.0, -1.0_f32] {
+            mesh.parametric(segments, 1, |u, v| {
+                // Going around the other way on the bottom keeps s x t pointing down
+                let angle = u * 2.0 * PI * side;
+                let outwards = Vec3::new(angle.cos(), 0.0, angle.sin());
+                let position = outwards * radius * v + Vec3::new(0.0, height * 0.5 * side, 0.0);
+                let tex_coords = Vec2::new(0.5 + outwards.x * v * 0.5, 0.5 + outwards.z * v * 0.5);
+                (position, Vec3::new(0.0, side, 0.0), tex_coords)
+            });
+        }
+        mesh
+    }
+
+    // Flat ring on the XZ plane facing +Y. u goes from the inner to the outer edge and v around
+    // the ring, radial subdivisions keep the affine interpolation of u close to the real radius.
+    pub fn annulus(inner_radius: f32, outer_radius: f32, segments: usize, rings: usize) -> Self {
+        let mut mesh = Mesh::empty();
+        mesh.parametric(segments.max(3), rings.max(1), |around, radial| {
+            let angle = around * 2.0 * PI;
+            let outwards = Vec3::new(angle.cos(), 0.0, angle.sin());
+            let radius = inner_radius + (outer_radius - inner_radius) * radial;
+            (outwards * radius, Vec3::new(0.0, 1.0, 0.0), Vec2::new(radial, around))
+        });
+        mesh
+    }
+
+    pub fn vertex_array(&self) -> Vec<Vertex> {
+        let (tangents, bitangents) = compute_tangents(&self.positions, &self.normals, &self.texcoords, &self.indices);
+        self.indices.iter().map(|&index| {
+            let index = index as usize;
+            let mut vertex = Vertex::new(self.positions[index], self.normals[index], self.texcoords[index]);
+            vertex.tangent = tangents[index];
+            vertex.bitangent = bitangents[index];
+            vertex
+        }).collect()
+    }
+}
@@ -0,0 +1,212 @@
+
+use nalgebra_glm::{Vec2, Vec3};
+use fastnoise_lite::{FastNoiseLite, NoiseType, FractalType, CellularDistanceFunction, CellularReturnType};
+use std::f32::consts::PI;
+
+// Noise at an object space position: continuous over the whole surface, no mirroring
+// between the front and the back like with 2D noise on x/y
+pub fn sample_3d(noise: &FastNoiseLite, position: &Vec3, zoom: f32, offset: &Vec3) -> f32 {
+    let p = position * zoom + offset;
+    noise.get_noise_3d(p.x, p.y, p.z)
+}
+
+// Like sample_3d but on the position projected to the unit sphere, so patterns keep
+// their size on displaced or non-spherical meshes
+pub fn sample_sphere(noise: &FastNoiseLite, position: &Vec3, zoom: f32, offset: &Vec3) -> f32 {
+    if position.magnitude() == 0.0 {
+        return sample_3d(noise, position, zoom, offset);
+    }
+    sample_3d(noise, &position.normalize(), zoom, offset)
+}
+
+// Longitude and latitude of a direction, both mapped to 0..1 with v = 0 at the north pole
+pub fn lat_long(position: &Vec3) -> Vec2 {
+    let direction = position.normalize();
+    let u = 0.5 + direction.z.atan2(direction.x) / (2.0 * PI);
+    let v = 0.5 - direction.y.clamp(-1.0, 1.0).asin() / PI;
+    Vec2::new(u, v)
+}
+
+// Inverse of lat_long, unit direction for a longitude/latitude pair
+pub fn lat_long_to_direction(uv: &Vec2) -> Vec3 {
+    let longitude = (uv.x - 0.5) * 2.0 * PI;
+    let latitude = (0.5 - uv.y) * PI;
+    Vec3::new(
+        longitude.cos() * latitude.cos(),
+        latitude.sin(),
+        longitude.sin() * latitude.cos(),
+    )
+}
+
+// Octave settings for the fractal helpers below. These compose octaves by hand on top of any
+// generator, so the base FastNoiseLite should usually have no fractal type of its own.
+#[derive(Debug, Clone, Copy)]
+pub struct Fractal {
+    pub octaves: u32,
+    pub lacunarity: f32,
+    pub gain: f32,
+}
+
+impl Default for Fractal {
+    fn default() -> Self {
+        Fractal {
+            octaves: 5,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
+
+impl Fractal {
+    pub fn new(octaves: u32, lacunarity: f32, gain: f32) -> Self {
+        Fractal { octaves, lacunarity, gain }
+    }
+
+    // Calls the closure with the frequency and amplitude of every octave and returns the
+    // sum of amplitudes, used to bring results back to the base noise range
+    fn octaves(&self, mut octave: impl FnMut(f32, f32)) -> f32 {
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        for _ in 0..self.octaves {
+            octave(frequency, amplitude);
+            total += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+        total
+    }
+}
+
+fn noise_at(noise: &FastNoiseLite, p: &Vec3, frequency: f32) -> f32 {
+    noise.get_noise_3d(p.x * frequency, p.y * frequency, p.z * frequency)
+}
+
+// Fractal Brownian motion, -1..1
+pub fn fbm(noise: &FastNoiseLite, p: &Vec3, fractal: &Fractal) -> f32 {
+    let mut sum = 0.0;
+    let total = fractal.octaves(|frequency, amplitude| {
+        sum += noise_at(noise, p, frequency) * amplitude;
+    });
+    if total > 0.0 { sum / total } else { 0.0 }
+}
+
+// Puffy rounded shapes, -1..1
+pub fn billow(noise: &FastNoiseLite, p: &Vec3, fractal: &Fractal) -> f32 {
+    let mut sum = 0.0;
+    let total = fractal.octaves(|frequency, amplitude| {
+        sum += (noise_at(noise, p, frequency).abs() * 2.0 - 1.0) * amplitude;
+    });
+    if total > 0.0 { sum / total } else { 0.0 }
+}
+
+// Sum of absolute octaves, 0..1, creased look for fire and gas
+pub fn turbulence(noise: &FastNoiseLite, p: &Vec3, fractal: &Fractal) -> f32 {
+    let mut sum = 0.0;
+    let total = fractal.octaves(|frequency, amplitude| {
+        sum += noise_at(noise, p, frequency).abs() * amplitude;
+    });
+    if total > 0.0 { sum / total } else { 0.0 }
+}
+
+// Ridged multifractal, 0..1: sharp crests where the noise crosses zero, and each octave is
+// weighted by the previous one so detail gathers on the ridges like eroded mountains
+pub fn ridged(noise: &FastNoiseLite, p: &Vec3, fractal: &Fractal) -> f32 {
+    let mut sum = 0.0;
+    let mut weight = 1.0;
+    let total = fractal.octaves(|frequency, amplitude| {
+        let ridge = 1.0 - noise_at(noise, p, frequency).abs();
+        let signal = ridge * ridge * weight;
+        weight = (signal * 2.0).clamp(0.0, 1.0);
+        sum += signal * amplitude;
+    });
+    if total > 0.0 { sum / total } else { 0.0 }
+}
+
+// Offsets the point by fBm sampled at decorrelated positions, feeding the result to any
+// other noise function swirls it like fluid
+pub fn domain_warp(noise: &FastNoiseLite, p: &Vec3, strength: f32, fractal: &Fractal) -> Vec3 {
+    // offsets are in noise space, so they stay decorrelated whatever the frequency
+    let offset_x = Vec3::new(0.0, 0.0, 0.0);
+    let offset_y = Vec3::new(5.2, 1.3, 2.8) / noise.frequency;
+    let offset_z = Vec3::new(1.7, 9.2, 4.1) / noise.frequency;
+
+    p + Vec3::new(
+        fbm(noise, &(p + offset_x), fractal),
+        fbm(noise, &(p + offset_y), fractal),
+        fbm(noise, &(p + offset_z), fractal),
+    ) * strength
+}
+
+// Everything needed to build a FastNoiseLite generator, so shaders can declare their
+// noise as data and build it once instead of every frame
+#[derive(Debug, Clone, Copy)]
+pub struct NoiseConfig {
+    pub noise_type: NoiseType,
+    pub seed: i32,
+    pub frequency: f32,
+    pub fractal_type: FractalType,
+    pub octaves: i32,
+    pub lacunarity: f32,
+    pub gain: f32,
+    pub cellular_distance: CellularDistanceFunction,
+    pub cellular_return: CellularReturnType,
+}
+
+impl Default for NoiseConfig {
+    fn default() -> Self {
+        NoiseConfig {
+            noise_type: NoiseType::OpenSimplex2,
+            seed: 1337,
+            frequency: 0.01,
+            fractal_type: FractalType::None,
+            octaves: 3,
+            lacunarity: 2.0,
+            gain: 0.5,
+            cellular_distance: CellularDistanceFunction::EuclideanSq,
+            cellular_return: CellularReturnType::Distance,
+        }
+    }
+}
+
+impl NoiseConfig {
+    pub fn new(noise_type: NoiseType, seed: i32) -> Self {
+        NoiseConfig {
+            noise_type,
+            seed,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_frequency(mut self, frequency: f32) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    pub fn with_fractal(mut self, fractal_type: FractalType, octaves: i32, lacunarity: f32, gain: f32) -> Self {
+        self.fractal_type = fractal_type;
+        self.octaves = octaves;
+        self.lacunarity = lacunarity;
+        self.gain = gain;
+        self
+    }
+
+    pub fn with_cellular(mut self, distance: CellularDistanceFunction, return_type: CellularReturnType) -> Self {
+        self.cellular_distance = distance;
+        self.cellular_return = return_type;
+        self
+    }
+
+    pub fn build(&self) -> FastNoiseLite {
+        let mut noise = FastNoiseLite::with_seed(self.seed);
+        noise.set_noise_type(Some(self.noise_type));
+        noise.set_frequency(Some(self.frequency));
+        noise.set_fractal_type(Some(self.fractal_type));
+        noise.set_fractal_octaves(Some(self.octaves));
+        noise.set_fractal_lacunarity(Some(self.lacunarity));
+        noise.set_fractal_gain(Some(self.gain));
+        noise.set_cellular_distance_function(Some(self.cellular_distance));
+        noise.set_cellular_return_type(Some(self.cellular_return));
+        noise
+    }
+}
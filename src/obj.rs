@@ -1,15 +1,26 @@
-use tobj;
 use nalgebra_glm::{Vec2, Vec3};
+use std::collections::HashMap;
+use std::ops::Range;
 use crate::vertex::Vertex;
+use crate::frustum::Aabb;
+use crate::mesh::Mesh;
 
 pub struct Obj {
-    meshes: Vec<Mesh>,
+    models: Vec<Model>,
 }
 
-struct Mesh {
+// Run of nearby triangles in a vertex array, culled as a whole
+pub struct Cluster {
+    pub vertices: Range<usize>,
+    pub bounds: Aabb,
+}
+
+struct Model {
     vertices: Vec<Vec3>,
     normals: Vec<Vec3>,
     texcoords: Vec<Vec2>,
+    tangents: Vec<Vec3>,
+    bitangents: Vec<Vec3>,
     indices: Vec<u32>,
 }
 
@@ -21,29 +32,59 @@ impl Obj {
             ..Default::default()
         })?;
 
-        let meshes = models.into_iter().map(|model| {
+        let models = models.into_iter().map(|model| {
             let mesh = model.mesh;
-            Mesh {
-                vertices: mesh.positions.chunks(3)
-                    .map(|v| Vec3::new(v[0], v[1], v[2]))
-                    .collect(),
-                normals: mesh.normals.chunks(3)
-                    .map(|n| Vec3::new(n[0], n[1], n[2]))
-                    .collect(),
-                texcoords: mesh.texcoords.chunks(2)
-                    .map(|t| Vec2::new(t[0], 1.0 - t[1]))
-                    .collect(),
+            let vertices: Vec<Vec3> = mesh.positions.chunks(3)
+                .map(|v| Vec3::new(v[0], v[1], v[2]))
+                .collect();
+            let normals: Vec<Vec3> = mesh.normals.chunks(3)
+                .map(|n| Vec3::new(n[0], n[1], n[2]))
+                .collect();
+            let texcoords: Vec<Vec2> = mesh.texcoords.chunks(2)
+                .map(|t| Vec2::new(t[0], 1.0 - t[1]))
+                .collect();
+            let (tangents, bitangents) = compute_tangents(&vertices, &normals, &texcoords, &mesh.indices);
+
+            Model {
+                vertices,
+                normals,
+                texcoords,
+                tangents,
+                bitangents,
                 indices: mesh.indices,
             }
         }).collect();
 
-        Ok(Obj { meshes })
+        Ok(Obj { models })
+    }
+
+    // Wraps a generated mesh, so it gets the same tangents and clustering as loaded models
+    pub fn from_mesh(mesh: Mesh) -> Self {
+        let (tangents, bitangents) = compute_tangents(&mesh.positions, &mesh.normals, &mesh.texcoords, &mesh.indices);
+        Obj {
+            models: vec![Model {
+                vertices: mesh.positions,
+                normals: mesh.normals,
+                texcoords: mesh.texcoords,
+                tangents,
+                bitangents,
+                indices: mesh.indices,
+            }],
+        }
+    }
+
+    // Distance of the farthest vertex from the origin
+    pub fn radius(&self) -> f32 {
+        self.models.iter()
+            .flat_map(|model| &model.vertices)
+            .map(|vertex| vertex.magnitude())
+            .fold(0.0, f32::max)
     }
 
     pub fn get_vertex_array(&self) -> Vec<Vertex> {
         let mut vertices = Vec::new();
 
-        for mesh in &self.meshes {
+        for mesh in &self.models {
             for &index in &mesh.indices {
                 let position = mesh.vertices[index as usize];
                 let normal = mesh.normals.get(index as usize)
@@ -53,10 +94,164 @@ impl Obj {
                     .cloned()
                     .unwrap_or(Vec2::new(0.0, 0.0));
 
-                vertices.push(Vertex::new(position, normal, tex_coords));
+                let mut vertex = Vertex::new(position, normal, tex_coords);
+                vertex.tangent = mesh.tangents[index as usize];
+                vertex.bitangent = mesh.bitangents[index as usize];
+                vertices.push(vertex);
             }
         }
 
         vertices
     }
+
+    // Coarser copy for distant objects by vertex clustering. The bounding box of every model is
+    // cut into cubes, cells across its longest side, and the vertices in each cube merge into
+    // their average. Triangles that collapse are dropped. UVs are averaged too, so faces along
+    // a texture seam get smeared, which is fine at the sizes this is meant for.
+    pub fn simplified(&self, cells: usize) -> Obj {
+        let models = self.models.iter().map(|model| {
+            let bounds = Aabb::from_points(&model.vertices);
+            let cell_size = (bounds.max - bounds.min).max().max(f32::EPSILON) / cells.max(1) as f32;
+
+            let mut cell_index: HashMap<(i32, i32, i32), usize> = HashMap::new();
+            let mut sums: Vec<(Vec3, Vec3, Vec2, f32)> = Vec::new();
+            let remap: Vec<u32> = model.vertices.iter().enumerate().map(|(i, position)| {
+                let cell = (position - bounds.min) / cell_size;
+                let key = (cell.x.floor() as i32, cell.y.floor() as i32, cell.z.floor() as i32);
+                let index = *cell_index.entry(key).or_insert_with(|| {
+                    sums.push((Vec3::zeros(), Vec3::zeros(), Vec2::zeros(), 0.0));
+                    sums.len() - 1
+                });
+                let sum = &mut sums[index];
+                sum.0 += position;
+                sum.1 += model.normals.get(i).cloned().unwrap_or(Vec3::zeros());
+                sum.2 += model.texcoords.get(i).cloned().unwrap_or(Vec2::zeros());
+                sum.3 += 1.0;
+                index as u32
+            }).collect();
+
+            let vertices: Vec<Vec3> = sums.iter().map(|(position, _, _, count)| position / *count).collect();
+            let normals: Vec<Vec3> = sums.iter().map(|(_, normal, _, _)| normal.try_normalize(f32::EPSILON).unwrap_or(Vec3::new(0.0, 1.0, 0.0))).collect();
+            let texcoords: Vec<Vec2> = sums.iter().map(|(_, _, uv, count)| uv / *count).collect();
+            let indices: Vec<u32> = model.indices.chunks_exact(3)
+                .map(|triangle| [remap[triangle[0] as usize], remap[triangle[1] as usize], remap[triangle[2] as usize]])
+                .filter(|[a, b, c]| a != b && b != c && a != c)
+                .flatten()
+                .collect();
+            let (tangents, bitangents) = compute_tangents(&vertices, &normals, &texcoords, &indices);
+
+            Model { vertices, normals, texcoords, tangents, bitangents, indices }
+        }).collect();
+
+        Obj { models }
+    }
+
+    // Same triangles as get_vertex_array, reordered so every cluster of at most
+    // triangles_per_cluster is spatially compact and gets a tight bounding box
+    pub fn get_clustered_vertex_array(&self, triangles_per_cluster: usize) -> (Vec<Vertex>, Vec<Cluster>) {
+        let vertex_array = self.get_vertex_array();
+        let mut triangles: Vec<&[Vertex]> = vertex_array.chunks_exact(3).collect();
+
+        let mut groups = Vec::new();
+        split_triangles(&mut triangles, triangles_per_cluster.max(1), &mut groups);
+
+        let mut vertices = Vec::with_capacity(vertex_array.len());
+        let mut clusters = Vec::with_capacity(groups.len());
+        for group in groups {
+            let start = vertices.len();
+            for triangle in group {
+                vertices.extend_from_slice(triangle);
+            }
+            let bounds = Aabb::from_points(vertices[start..].iter().map(|vertex| &vertex.position));
+            clusters.push(Cluster { vertices: start..vertices.len(), bounds });
+        }
+
+        (vertices, clusters)
+    }
+}
+
+// Median split along the longest axis of the triangle centroids, until every group is small
+fn split_triangles<'a>(triangles: &mut [&'a [Vertex]], max_triangles: usize, groups: &mut Vec<Vec<&'a [Vertex]>>) {
+    if triangles.len() <= max_triangles {
+        groups.push(triangles.to_vec());
+        return;
+    }
+
+    let centroid = |triangle: &[Vertex]| (triangle[0].position + triangle[1].position + triangle[2].position) / 3.0;
+    let centroids: Vec<Vec3> = triangles.iter().map(|triangle| centroid(triangle)).collect();
+    let extent = Aabb::from_points(&centroids);
+    let size = extent.max - extent.min;
+    let axis = if size.x >= size.y && size.x >= size.z { 0 } else if size.y >= size.z { 1 } else { 2 };
+
+    triangles.sort_by(|a, b| centroid(a)[axis].total_cmp(&centroid(b)[axis]));
+    let (left, right) = triangles.split_at_mut(triangles.len() / 2);
+    split_triangles(left, max_triangles, groups);
+    split_triangles(right, max_triangles, groups);
+}
+
+// Per-vertex tangent frames in the spirit of MikkTSpace: triangle tangents are accumulated
+// on shared vertices, orthogonalized against the normal and the bitangent keeps the UV handedness.
+// Missing normals are taken from the faces, missing or degenerate UVs get an arbitrary frame.
+pub fn compute_tangents(positions: &[Vec3], normals: &[Vec3], texcoords: &[Vec2], indices: &[u32]) -> (Vec<Vec3>, Vec<Vec3>) {
+    let mut tangent_sums = vec![Vec3::zeros(); positions.len()];
+    let mut bitangent_sums = vec![Vec3::zeros(); positions.len()];
+    let mut face_normals = vec![Vec3::zeros(); positions.len()];
+
+    for tri in indices.chunks_exact(3) {
+        let (i0, i1, i2) = (tri[0] as usize, tri[1] as usize, tri[2] as usize);
+        let edge1 = positions[i1] - positions[i0];
+        let edge2 = positions[i2] - positions[i0];
+
+        let face_normal = edge1.cross(&edge2);
+        for &i in &[i0, i1, i2] {
+            face_normals[i] += face_normal;
+        }
+
+        if texcoords.len() < positions.len() {
+            continue;
+        }
+
+        let duv1 = texcoords[i1] - texcoords[i0];
+        let duv2 = texcoords[i2] - texcoords[i0];
+        let det = duv1.x * duv2.y - duv2.x * duv1.y;
+        if det.abs() < 1e-8 {
+            continue;
+        }
+
+        let r = 1.0 / det;
+        let tangent = (edge1 * duv2.y - edge2 * duv1.y) * r;
+        let bitangent = (edge2 * duv1.x - edge1 * duv2.x) * r;
+
+        for &i in &[i0, i1, i2] {
+            tangent_sums[i] += tangent;
+            bitangent_sums[i] += bitangent;
+        }
+    }
+
+    let mut tangents = Vec::with_capacity(positions.len());
+    let mut bitangents = Vec::with_capacity(positions.len());
+
+    for i in 0..positions.len() {
+        let normal = match normals.get(i) {
+            Some(n) if n.magnitude() > 0.0 => n.normalize(),
+            _ if face_normals[i].magnitude() > 0.0 => face_normals[i].normalize(),
+            _ => Vec3::new(0.0, 1.0, 0.0),
+        };
+
+        // Gram-Schmidt against the normal
+        let t = tangent_sums[i] - normal * normal.dot(&tangent_sums[i]);
+        let tangent = if t.magnitude() > 1e-6 {
+            t.normalize()
+        } else {
+            let helper = if normal.x.abs() < 0.9 { Vec3::new(1.0, 0.0, 0.0) } else { Vec3::new(0.0, 1.0, 0.0) };
+            (helper - normal * normal.dot(&helper)).normalize()
+        };
+
+        let handedness = if normal.cross(&tangent).dot(&bitangent_sums[i]) < 0.0 { -1.0 } else { 1.0 };
+
+        tangents.push(tangent);
+        bitangents.push(normal.cross(&tangent) * handedness);
+    }
+
+    (tangents, bitangents)
 }
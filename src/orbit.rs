@@ -0,0 +1,112 @@
+
+use nalgebra_glm::{Vec3, rotate_vec3};
+use std::f32::consts::PI;
+
+// Rotation of a body around its own axis, tilted away from the orbital plane
+#[derive(Debug, Clone, Copy)]
+pub struct Spin {
+    // Radians between the spin axis and the Y axis
+    pub axial_tilt: f32,
+    // Seconds per turn
+    pub period: f32,
+    // Starting angle, in turns
+    pub phase: f32,
+}
+
+impl Spin {
+    pub fn new(axial_tilt: f32, period: f32) -> Self {
+        Spin { axial_tilt, period, phase: 0.0 }
+    }
+
+    // Euler angles for create_model_matrix. Its order is Z * Y * X, so the spin around Y happens
+    // first and the tilt around Z leans the spinning body over.
+    pub fn rotation_at(&self, time: f32) -> Vec3 {
+        let angle = (time / self.period + self.phase) * 2.0 * PI;
+        Vec3::new(0.0, angle.rem_euclid(2.0 * PI), self.axial_tilt)
+    }
+}
+
+// Keplerian orbit around another scene object. Circular orbits are the eccentricity 0 case.
+#[derive(Debug, Clone, Copy)]
+pub struct Orbit {
+    // Index of the parent in the scene, parents have to come before their children
+    pub parent: usize,
+    pub semi_major_axis: f32,
+    pub eccentricity: f32,
+    // Seconds per revolution
+    pub period: f32,
+    // Mean anomaly at time 0, in turns
+    pub phase: f32,
+    // Tilt of the orbital plane around X, in radians
+    pub inclination: f32,
+    // Rotation of the orbital plane around Y, in radians
+    pub ascending_node: f32,
+    // Angle from the ascending node to the closest point of the orbit, in radians
+    pub argument_of_periapsis: f32,
+}
+
+impl Orbit {
+    pub fn circular(parent: usize, radius: f32, period: f32) -> Self {
+        Orbit::elliptical(parent, radius, 0.0, period)
+    }
+
+    pub fn elliptical(parent: usize, semi_major_axis: f32, eccentricity: f32, period: f32) -> Self {
+        Orbit {
+            parent,
+            semi_major_axis,
+            eccentricity: eccentricity.clamp(0.0, 0.99),
+            period,
+            phase: 0.0,
+            inclination: 0.0,
+            ascending_node: 0.0,
+            argument_of_periapsis: 0.0,
+        }
+    }
+
+    pub fn with_phase(mut self, phase: f32) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    pub fn with_plane(mut self, inclination: f32, ascending_node: f32) -> Self {
+        self.inclination = inclination;
+        self.ascending_node = ascending_node;
+        self
+    }
+
+    pub fn with_periapsis(mut self, argument_of_periapsis: f32) -> Self {
+        self.argument_of_periapsis = argument_of_periapsis;
+        self
+    }
+
+    // Farthest distance from the parent
+    pub fn apoapsis(&self) -> f32 {
+        self.semi_major_axis * (1.0 + self.eccentricity)
+    }
+
+    // Offset from the parent at the given time
+    pub fn position_at(&self, time: f32) -> Vec3 {
+        let e = self.eccentricity;
+        let mean_anomaly = ((time / self.period + self.phase) * 2.0 * PI).rem_euclid(2.0 * PI);
+
+        // Kepler's equation M = E - e sin E, solved with a few Newton steps
+        let mut eccentric_anomaly = if e > 0.8 { PI } else { mean_anomaly };
+        for _ in 0..8 {
+            let error = eccentric_anomaly - e * eccentric_anomaly.sin() - mean_anomaly;
+            eccentric_anomaly -= error / (1.0 - e * eccentric_anomaly.cos());
+        }
+
+        // In the orbital plane with the parent at one focus, x pointing to the periapsis
+        let a = self.semi_major_axis;
+        let in_plane = Vec3::new(
+            a * (eccentric_anomaly.cos() - e),
+            0.0,
+            -a * (1.0 - e * e).sqrt() * eccentric_anomaly.sin(),
+        );
+
+        let y_axis = Vec3::new(0.0, 1.0, 0.0);
+        let oriented = rotate_vec3(&in_plane, self.argument_of_periapsis, &y_axis);
+        let inclined = rotate_vec3(&oriented, self.inclination, &Vec3::new(1.0, 0.0, 0.0));
+        rotate_vec3(&inclined, self.ascending_node, &y_axis)
+    }
+}
@@ -0,0 +1,60 @@
+
+use nalgebra_glm::{Vec3, dot};
+use fastnoise_lite::{FastNoiseLite, NoiseType};
+use crate::Uniforms;
+use crate::vertex::Vertex;
+use crate::mesh::Mesh;
+use crate::fragment::Fragment;
+use crate::color::Color;
+use crate::gradient::{Gradient, BlendSpace};
+use crate::noise::{NoiseConfig, Fractal, fbm};
+
+// Rings attached to a planet, in the planet object space
+pub struct Ring {
+    pub outer_radius: f32,
+    // Rotation relative to the planet, tilts the ring plane away from its equator
+    pub tilt: Vec3,
+    pub palette: Gradient,
+    pub density: f32,
+    noise: FastNoiseLite,
+    pub mesh: Vec<Vertex>,
+}
+
+impl Ring {
+    pub fn new(inner_radius: f32, outer_radius: f32) -> Self {
+        Ring {
+            outer_radius,
+            tilt: Vec3::new(0.0, 0.0, 0.0),
+            palette: Gradient::from_hex(&["#6e5a44", "#c8b08a", "#e6d6b4", "#a08a6a", "#8c7a64"])
+                .expect("valid palette")
+                .in_space(BlendSpace::Perceptual),
+            density: 0.9,
+            noise: NoiseConfig::new(NoiseType::OpenSimplex2, 1337).build(),
+            mesh: Mesh::annulus(inner_radius, outer_radius, 96, 8).vertex_array(),
+        }
+    }
+
+    pub fn with_tilt(mut self, tilt: Vec3) -> Self {
+        self.tilt = tilt;
+        self
+    }
+}
+
+// Color and opacity of a ring fragment, with fragment.shadow already holding the planet shadow
+pub fn ring_shader(fragment: &Fragment, uniforms: &Uniforms, ring: &Ring) -> (Color, f32) {
+    let radial = fragment.tex_coords.x;
+
+    // Many thin bands from noise along the radius only, plus a Cassini-like gap
+    let fractal = Fractal::new(4, 2.3, 0.6);
+    let bands = fbm(&ring.noise, &Vec3::new(radial * 900.0, 0.0, 0.0), &fractal) * 0.5 + 0.5;
+    let gap = ((radial - 0.62).abs() / 0.04).min(1.0);
+    let edges = (radial / 0.05).min(1.0) * ((1.0 - radial) / 0.05).min(1.0);
+    let opacity = (bands * 1.4 - 0.15).clamp(0.0, 1.0) * gap * edges * ring.density;
+
+    // Ice and dust scatter light whichever side of the ring plane the sun is on
+    let light_dir = uniforms.light.direction_to(&fragment.world_position);
+    let lit = (dot(&fragment.normal, &light_dir).abs() * 0.8 + 0.2) * uniforms.light.intensity * fragment.shadow;
+
+    let color = ring.palette.sample(radial * 0.8 + bands * 0.2);
+    (color * lit, opacity)
+}
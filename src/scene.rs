@@ -0,0 +1,128 @@
+
+use nalgebra_glm::Vec3;
+use crate::shaders::{Displacement, Shader, ShaderKind};
+use crate::atmosphere::Atmosphere;
+use crate::ring::Ring;
+use crate::orbit::{Orbit, Spin};
+
+pub struct SceneObject {
+    pub translation: Vec3,
+    pub rotation: Vec3,
+    pub scale: f32,
+    pub shader: Shader,
+    pub displacement: Option<Displacement>,
+    pub atmosphere: Option<Atmosphere>,
+    pub rings: Option<Ring>,
+    pub spin: Option<Spin>,
+    pub orbit: Option<Orbit>,
+    // Level of detail drawn last frame
+    pub lod: usize,
+}
+
+impl SceneObject {
+    pub fn new(translation: Vec3, rotation: Vec3, scale: f32) -> Self {
+        SceneObject {
+            translation,
+            rotation,
+            scale,
+            shader: Shader::new(ShaderKind::BlackAndWhite),
+            displacement: None,
+            atmosphere: None,
+            rings: None,
+            spin: None,
+            orbit: None,
+            lod: 0,
+        }
+    }
+
+    pub fn with_shader(mut self, shader: Shader) -> Self {
+        self.shader = shader;
+        self
+    }
+
+    pub fn with_displacement(mut self, displacement: Displacement) -> Self {
+        self.displacement = Some(displacement);
+        self
+    }
+
+    pub fn with_atmosphere(mut self, atmosphere: Atmosphere) -> Self {
+        self.atmosphere = Some(atmosphere);
+        self
+    }
+
+    pub fn with_spin(mut self, spin: Spin) -> Self {
+        self.spin = Some(spin);
+        self
+    }
+
+    pub fn with_orbit(mut self, orbit: Orbit) -> Self {
+        self.orbit = Some(orbit);
+        self
+    }
+
+    // Center and radius of a sphere enclosing the object with its displacement, rings and
+    // atmosphere, for a mesh reaching mesh_radius from its origin
+    pub fn bounding_sphere(&self, mesh_radius: f32) -> (Vec3, f32) {
+        let displacement = self.displacement.as_ref().map_or(0.0, |d| d.amplitude);
+        let rings = self.rings.as_ref().map_or(0.0, |r| r.outer_radius);
+        let atmosphere = self.atmosphere.as_ref().map_or(0.0, |a| a.thickness);
+        let extent = (mesh_radius + displacement).max(rings).max(mesh_radius * (1.0 + atmosphere));
+        (self.translation, self.scale * extent)
+    }
+
+    // Whether the object looks different as time goes by
+    pub fn is_animated(&self) -> bool {
+        self.spin.is_some()
+            || self.orbit.is_some()
+            || self.shader.kind.is_animated()
+            || self.displacement.as_ref().is_some_and(|d| d.is_animated())
+    }
+}
+
+// Orbits are resolved in order, so every parent has to be an earlier object. Checked once when
+// the scene is built instead of on every frame.
+pub fn check_orbits(objects: &[SceneObject]) -> Result<(), String> {
+    for (i, object) in objects.iter().enumerate() {
+        if let Some(orbit) = object.orbit {
+            if orbit.parent >= i {
+                return Err(format!("object {} orbits object {}, parents have to come before their children", i, orbit.parent));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Moves every animated object to where it is at the given time in seconds. Objects without
+// spin or orbit keep the rotation and translation they were created with.
+pub fn animate(objects: &mut [SceneObject], time: f32) {
+    for i in 0..objects.len() {
+        if let Some(spin) = objects[i].spin {
+            objects[i].rotation = spin.rotation_at(time);
+        }
+        if let Some(orbit) = objects[i].orbit {
+            if let Some(parent) = objects[..i].get(orbit.parent).map(|parent| parent.translation) {
+                objects[i].translation = parent + orbit.position_at(time);
+            }
+        }
+    }
+}
+
+// Center and radius of a sphere enclosing every object, drawn with meshes up to mesh_radius.
+// Orbiting objects count with their whole orbit, so the bounds hold while they move.
+pub fn scene_bounds(objects: &[SceneObject], mesh_radius: f32) -> (Vec3, f32) {
+    let center = Vec3::new(0.0, 0.0, 0.0);
+    let mut reach: Vec<f32> = Vec::with_capacity(objects.len());
+    for object in objects {
+        let distance = match object.orbit {
+            Some(orbit) => reach.get(orbit.parent).copied().unwrap_or(0.0) + orbit.apoapsis(),
+            None => (object.translation - center).magnitude(),
+        };
+        reach.push(distance);
+    }
+
+    let radius = objects.iter().zip(&reach)
+        .map(|(object, distance)| distance + object.bounding_sphere(mesh_radius).1)
+        .fold(0.0, f32::max);
+
+    (center, radius)
+}
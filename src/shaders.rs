@@ -1,23 +1,128 @@
 
-use nalgebra_glm::{Vec3, Vec4, Mat3, dot, mat4_to_mat3};
+use nalgebra_glm::{Vec3, Vec4, Mat3, dot, mat4_to_mat3, rotate_y_vec3};
 use crate::vertex::Vertex;
 use crate::Uniforms;
 use crate::fragment::Fragment;
 use crate::color::Color;
+use crate::texture::Texture;
+use crate::bake::TextureMapping;
+use crate::atmosphere::Atmosphere;
+use crate::ring::Ring;
+use crate::gradient::{Gradient, Interpolation, BlendSpace};
+use crate::noise::{NoiseConfig, Fractal, sample_3d, sample_sphere, ridged, billow, turbulence, domain_warp};
+use fastnoise_lite::{FastNoiseLite, NoiseType, FractalType, CellularDistanceFunction, CellularReturnType};
 use std::f32::consts::PI;
 use rand::Rng;
 use rand::SeedableRng;
 use rand::rngs::StdRng;
 
+#[derive(Debug, Clone, Copy)]
+pub enum DisplacementKind {
+    // Ridged peaks that only push outwards
+    Mountains,
+    // Smooth swelling blobs that move with time
+    LavaBulges,
+}
+
+pub struct Displacement {
+    pub kind: DisplacementKind,
+    pub amplitude: f32,
+    pub zoom: f32,
+    // Noise units per second
+    pub speed: f32,
+    pub fractal: Fractal,
+    noise: FastNoiseLite,
+}
+
+impl Displacement {
+    pub fn mountains(amplitude: f32, zoom: f32) -> Self {
+        Displacement {
+            kind: DisplacementKind::Mountains,
+            amplitude,
+            zoom,
+            speed: 0.0,
+            fractal: Fractal::new(4, 2.0, 0.5),
+            noise: NoiseConfig::default().build(),
+        }
+    }
+
+    pub fn lava_bulges(amplitude: f32, zoom: f32, speed: f32) -> Self {
+        Displacement {
+            kind: DisplacementKind::LavaBulges,
+            amplitude,
+            zoom,
+            speed,
+            fractal: Fractal::new(1, 2.0, 0.5),
+            noise: NoiseConfig::default().build(),
+        }
+    }
+
+    pub fn with_noise(mut self, config: &NoiseConfig) -> Self {
+        self.noise = config.build();
+        self
+    }
+
+    pub fn with_fractal(mut self, fractal: Fractal) -> Self {
+        self.fractal = fractal;
+        self
+    }
+
+    pub fn is_animated(&self) -> bool {
+        matches!(self.kind, DisplacementKind::LavaBulges) && self.speed != 0.0
+    }
+
+    // Offset along the normal for an object space point
+    fn height(&self, point: &Vec3, uniforms: &Uniforms) -> f32 {
+        match self.kind {
+            DisplacementKind::Mountains => {
+                ridged(&self.noise, &(point * self.zoom), &self.fractal) * self.amplitude
+            }
+            DisplacementKind::LavaBulges => {
+                let t = uniforms.time * self.speed;
+                let p = point * self.zoom + Vec3::new(t, 0.0, -t);
+                let bulge = billow(&self.noise, &p, &self.fractal) * 0.5 + 0.5;
+                bulge * self.amplitude
+            }
+        }
+    }
+}
+
+// Moves the vertex along its normal and rebuilds the tangent frame from two nearby
+// displaced points, so lighting follows the new surface instead of the original sphere
+fn displace_vertex(vertex: &Vertex, displacement: &Displacement, uniforms: &Uniforms) -> (Vec3, Vec3, Vec3, Vec3) {
+    let epsilon = 0.005;
+    let displace = |p: Vec3| p + vertex.normal * displacement.height(&p, uniforms);
+
+    let position = displace(vertex.position);
+    let along_tangent = displace(vertex.position + vertex.tangent * epsilon) - position;
+    let along_bitangent = displace(vertex.position + vertex.bitangent * epsilon) - position;
+
+    let mut normal = along_tangent.cross(&along_bitangent).normalize();
+    if dot(&normal, &vertex.normal) < 0.0 {
+        normal = -normal;
+    }
+    let tangent = along_tangent.normalize();
+    let bitangent = along_bitangent.normalize();
+
+    (position, normal, tangent, bitangent)
+}
+
 pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
+    let (object_position, object_normal, object_tangent, object_bitangent) = match uniforms.displacement {
+        Some(displacement) => displace_vertex(vertex, displacement, uniforms),
+        None => (vertex.position, vertex.normal, vertex.tangent, vertex.bitangent),
+    };
+
     let position = Vec4::new(
-        vertex.position.x,
-        vertex.position.y,
-        vertex.position.z,
+        object_position.x,
+        object_position.y,
+        object_position.z,
         1.0
     );
 
-    let transformed = uniforms.projection_matrix * uniforms.view_matrix * uniforms.model_matrix * position;
+    let world = uniforms.model_matrix * position;
+    let view = uniforms.view_matrix * world;
+    let transformed = uniforms.projection_matrix * view;
 
     let w = transformed.w;
     let transformed_position = Vec4::new(
@@ -29,162 +134,357 @@ pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
 
     let screen_position = uniforms.viewport_matrix * transformed_position;
 
+    // Normals go through the inverse-transpose so non-uniform scaling keeps them perpendicular
     let model_mat3 = mat4_to_mat3(&uniforms.model_matrix);
     let normal_matrix = model_mat3.transpose().try_inverse().unwrap_or(Mat3::identity());
+    let model_view_mat3 = mat4_to_mat3(&(uniforms.view_matrix * uniforms.model_matrix));
+    let view_normal_matrix = model_view_mat3.transpose().try_inverse().unwrap_or(Mat3::identity());
+
+    let world_normal = (normal_matrix * object_normal).normalize();
+    let view_normal = (view_normal_matrix * object_normal).normalize();
 
-    let transformed_normal = normal_matrix * vertex.normal;
+    // Tangents lie on the surface, so they follow the model matrix itself
+    let world_tangent = (model_mat3 * object_tangent).normalize();
+    let world_bitangent = (model_mat3 * object_bitangent).normalize();
 
     Vertex {
-        position: vertex.position,
-        normal: vertex.normal,
+        position: object_position,
+        normal: object_normal,
         tex_coords: vertex.tex_coords,
+        tangent: object_tangent,
+        bitangent: object_bitangent,
         color: vertex.color,
         transformed_position: Vec3::new(screen_position.x, screen_position.y, screen_position.z),
-        transformed_normal: transformed_normal
+        world_position: Vec3::new(world.x, world.y, world.z),
+        world_normal,
+        view_position: Vec3::new(view.x, view.y, view.z),
+        view_normal,
+        world_tangent,
+        world_bitangent
     }
 }
 
-pub fn fragment_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    black_and_white(fragment, uniforms)
-    // dalmata_shader(fragment, uniforms)
-    // cloud_shader(fragment, uniforms)
-    // cellular_shader(fragment, uniforms)
-    // lava_shader(fragment, uniforms)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShaderKind {
+    BlackAndWhite,
+    Dalmata,
+    Cloud,
+    Cellular,
+    Lava,
+    Crater,
+    GasGiant,
+    // Samples a baked texture instead of evaluating noise
+    Textured,
+}
+
+impl ShaderKind {
+    // Names accepted on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "blackandwhite" => Some(ShaderKind::BlackAndWhite),
+            "dalmata" => Some(ShaderKind::Dalmata),
+            "cloud" => Some(ShaderKind::Cloud),
+            "cellular" => Some(ShaderKind::Cellular),
+            "lava" => Some(ShaderKind::Lava),
+            "crater" => Some(ShaderKind::Crater),
+            "gasgiant" => Some(ShaderKind::GasGiant),
+            _ => None,
+        }
+    }
+
+    // Shaders that read uniforms.time and change from frame to frame
+    pub fn is_animated(&self) -> bool {
+        matches!(self, ShaderKind::BlackAndWhite | ShaderKind::Cloud | ShaderKind::Lava | ShaderKind::GasGiant)
+    }
+
+    // Noise generators each shader expects, in the order it samples them
+    pub fn default_noise(&self) -> Vec<NoiseConfig> {
+        match self {
+            ShaderKind::BlackAndWhite => vec![],
+            ShaderKind::Dalmata => vec![NoiseConfig::new(NoiseType::OpenSimplex2, 1337)],
+            ShaderKind::Cloud => vec![
+                NoiseConfig::new(NoiseType::OpenSimplex2, 1337)
+                    .with_fractal(FractalType::FBm, 4, 2.0, 0.5),
+            ],
+            ShaderKind::Cellular => vec![
+                NoiseConfig::new(NoiseType::Cellular, 1337)
+                    .with_frequency(0.3)
+                    .with_cellular(CellularDistanceFunction::Euclidean, CellularReturnType::CellValue),
+            ],
+            ShaderKind::Lava => vec![NoiseConfig::new(NoiseType::OpenSimplex2, 1337)],
+            // surface bumps, then dust color
+            ShaderKind::Crater => vec![
+                NoiseConfig::new(NoiseType::OpenSimplex2, 1337),
+                NoiseConfig::new(NoiseType::OpenSimplex2, 2024),
+            ],
+            ShaderKind::GasGiant => vec![NoiseConfig::new(NoiseType::OpenSimplex2, 99)],
+            ShaderKind::Textured => vec![],
+        }
+    }
+
+    // Air around the planets the shader stands for, if they have any
+    pub fn default_atmosphere(&self) -> Option<Atmosphere> {
+        match self {
+            // Thin dusty air like Mars
+            ShaderKind::Crater => Some(Atmosphere::new(Color::from_hex(0xd8a07a), 0.06, 0.5)),
+            // Earth-like blue sky
+            ShaderKind::Cloud => Some(Atmosphere::new(Color::from_hex(0x6aa8ff), 0.1, 1.0)),
+            ShaderKind::GasGiant => Some(Atmosphere::new(Color::from_hex(0xf0dcb4), 0.08, 0.7).with_falloff(1.5)),
+            _ => None,
+        }
+    }
+
+    // Saturn-like ring for the gas giant
+    pub fn default_rings(&self) -> Option<Ring> {
+        match self {
+            ShaderKind::GasGiant => Some(Ring::new(0.7, 1.25).with_tilt(Vec3::new(0.45, 0.0, 0.2))),
+            _ => None,
+        }
+    }
+
+    // Colors each shader maps its scalar field to
+    pub fn default_palette(&self) -> Gradient {
+        let palette = match self {
+            ShaderKind::BlackAndWhite => Gradient::from_hex_stops(&[(0.0, "#000000"), (0.5, "#ffffff")])
+                .map(|g| g.with_interpolation(Interpolation::Constant)),
+            // White spots below the threshold, black above
+            ShaderKind::Dalmata => Gradient::from_hex_stops(&[(0.0, "#ffffff"), (0.75, "#000000")])
+                .map(|g| g.with_interpolation(Interpolation::Constant)),
+            ShaderKind::Cloud => Gradient::from_hex_stops(&[(0.0, "#1e6191"), (0.6, "#ffffff")])
+                .map(|g| g.with_interpolation(Interpolation::Constant)),
+            // Dark olive, light, forest and yellow greens
+            ShaderKind::Cellular => Gradient::from_hex_stops(&[(0.0, "#556b2f"), (0.15, "#7cfc00"), (0.7, "#228b22"), (0.75, "#adff2f")])
+                .map(|g| g.with_interpolation(Interpolation::Constant)),
+            ShaderKind::Lava => Gradient::from_hex(&["#821400", "#ff7800", "#fff000"]),
+            ShaderKind::Crater => Gradient::from_hex(&["#8a3a1c", "#b0542e", "#d68c5a"])
+                .map(|g| g.in_space(BlendSpace::Perceptual)),
+            ShaderKind::GasGiant => Gradient::from_hex(&["#b0764a", "#e8d0a6", "#c9a27a", "#e8d0a6", "#b0764a"])
+                .map(|g| g.with_interpolation(Interpolation::Smoothstep).in_space(BlendSpace::Perceptual)),
+            // Flat color shown while there is no texture
+            ShaderKind::Textured => Gradient::from_hex(&["#808080"]),
+        };
+
+        palette.expect("built-in palettes are valid")
+    }
+}
+
+// A fragment shader together with the noise generators and palette it owns
+pub struct Shader {
+    pub kind: ShaderKind,
+    pub palette: Gradient,
+    noise: Vec<FastNoiseLite>,
+    texture: Option<(Texture, TextureMapping)>,
+}
+
+impl Shader {
+    pub fn new(kind: ShaderKind) -> Self {
+        Shader::with_noise(kind, &kind.default_noise())
+    }
+
+    // Same shader with its generators replaced, missing ones keep the defaults
+    pub fn with_noise(kind: ShaderKind, configs: &[NoiseConfig]) -> Self {
+        let defaults = kind.default_noise();
+        let noise = (0..defaults.len())
+            .map(|i| configs.get(i).unwrap_or(&defaults[i]).build())
+            .collect();
+
+        Shader { kind, palette: kind.default_palette(), noise, texture: None }
+    }
+
+    // Cheap runtime shader for a texture baked from one of the procedural ones
+    pub fn textured(texture: Texture, mapping: TextureMapping) -> Self {
+        Shader {
+            texture: Some((texture, mapping)),
+            ..Shader::new(ShaderKind::Textured)
+        }
+    }
+
+    pub fn with_palette(mut self, palette: Gradient) -> Self {
+        self.palette = palette;
+        self
+    }
 }
 
-fn black_and_white(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let seed = uniforms.time as f32 * fragment.vertex_position.y * fragment.vertex_position.x;
+pub fn fragment_shader(fragment: &Fragment, uniforms: &Uniforms, shader: &Shader) -> Color {
+    let noise = &shader.noise;
+    let palette = &shader.palette;
+    match shader.kind {
+        ShaderKind::BlackAndWhite => black_and_white(fragment, uniforms, palette),
+        ShaderKind::Dalmata => dalmata_shader(fragment, &noise[0], palette),
+        ShaderKind::Cloud => cloud_shader(fragment, uniforms, &noise[0], palette),
+        ShaderKind::Cellular => cellular_shader(fragment, &noise[0], palette),
+        ShaderKind::Lava => lava_shader(fragment, uniforms, &noise[0], palette),
+        ShaderKind::Crater => crater_shader(fragment, uniforms, &noise[0], &noise[1], palette),
+        ShaderKind::GasGiant => gas_giant_shader(fragment, uniforms, &noise[0], palette),
+        ShaderKind::Textured => textured_shader(fragment, shader.texture.as_ref(), palette),
+    }
+}
+
+fn black_and_white(fragment: &Fragment, uniforms: &Uniforms, palette: &Gradient) -> Color {
+    // New static 60 times per second
+    let frame = (uniforms.time * 60.0).floor();
+    let seed = frame * fragment.vertex_position.y * fragment.vertex_position.x;
   
     let mut rng = StdRng::seed_from_u64(seed.abs() as u64);
   
     let random_number = rng.gen_range(0..=100);
   
-    let black_or_white = if random_number < 50 {
-      Color::new(0, 0, 0)
-    } else {
-      Color::new(255, 255, 255)
-    };
+ 
+    let black_or_white = palette.sample(random_number as f32 / 100.0);
   
     black_or_white * fragment.intensity
 }
   
-fn dalmata_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let zoom = 100.0;
-    let ox = 0.0;
-    let oy = 0.0;
-    let x = fragment.vertex_position.x;
-    let y = fragment.vertex_position.y;
-  
-    let noise_value = uniforms.noise.get_noise_2d(
-      (x + ox) * zoom,
-      (y + oy) * zoom,
-    );
+fn dalmata_shader(fragment: &Fragment, noise: &FastNoiseLite, palette: &Gradient) -> Color {
+    let zoom = 50.0;
+    let offset = Vec3::new(0.0, 0.0, 0.0);
   
-    let spot_threshold = 0.5;
-    let spot_color = Color::new(255, 255, 255); // White
-    let base_color = Color::new(0, 0, 0); // Black
+    let noise_value = sample_sphere(noise, &fragment.vertex_position, zoom, &offset);
   
-    let noise_color = if noise_value < spot_threshold {
-      spot_color
-    } else {
-      base_color
-    };
+    // Spots and base color are split at the palette stops
+    let noise_color = palette.sample(noise_value * 0.5 + 0.5);
   
     noise_color * fragment.intensity
 }
   
-fn cloud_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let zoom = 100.0;  // to move our values 
-    let ox = 100.0; // offset x in the noise map
-    let oy = 100.0;
-    let x = fragment.vertex_position.x;
-    let y = fragment.vertex_position.y;
-    let t = uniforms.time as f32 * 0.5;
-  
-    let noise_value = uniforms.noise.get_noise_2d(x * zoom + ox + t, y * zoom + oy);
+fn cloud_shader(fragment: &Fragment, uniforms: &Uniforms, noise: &FastNoiseLite, palette: &Gradient) -> Color {
+    let zoom = 50.0;  // to move our values 
+    let t = uniforms.time * 0.03 * 2.0 * PI; // radians around the planet axis per second
   
-    // Define cloud threshold and colors
-    let cloud_threshold = 0.5; // Adjust this value to change cloud density
-    let cloud_color = Color::new(255, 255, 255); // White for clouds
-    let sky_color = Color::new(30, 97, 145); // Sky blue
+    // Clouds drift along the longitude by turning the sampled position around the axis
+    let position = rotate_y_vec3(&fragment.vertex_position, t);
+    let offset = Vec3::new(0.0, 0.0, 0.0);
+    let noise_value = sample_sphere(noise, &position, zoom, &offset);
   
-    // Determine if the pixel is part of a cloud or sky
-    let noise_color = if noise_value > cloud_threshold {
-      cloud_color
-    } else {
-      sky_color
-    };
+    // Sky and clouds, move the cloud stop in the palette to change cloud density
+    let noise_color = palette.sample(noise_value * 0.5 + 0.5);
   
     noise_color * fragment.intensity
 }
   
-fn cellular_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let zoom = 30.0;  // Zoom factor to adjust the scale of the cell pattern
-    let ox = 50.0;    // Offset x in the noise map
-    let oy = 50.0;    // Offset y in the noise map
-    let x = fragment.vertex_position.x;
-    let y = fragment.vertex_position.y;
+fn cellular_shader(fragment: &Fragment, noise: &FastNoiseLite, palette: &Gradient) -> Color {
+    let zoom = 15.0;  // Zoom factor to adjust the scale of the cell pattern
+    let offset = Vec3::new(50.0, 50.0, 0.0);  // Offset in the noise map
   
     // Use a cellular noise function to create the plant cell pattern
-    let cell_noise_value = uniforms.noise.get_noise_2d(x * zoom + ox, y * zoom + oy).abs();
-  
-    // Define different shades of green for the plant cells
-    let cell_color_1 = Color::new(85, 107, 47);   // Dark olive green
-    let cell_color_2 = Color::new(124, 252, 0);   // Light green
-    let cell_color_3 = Color::new(34, 139, 34);   // Forest green
-    let cell_color_4 = Color::new(173, 255, 47);  // Yellow green
-  
-    // Use the noise value to assign a different color to each cell
-    let final_color = if cell_noise_value < 0.15 {
-      cell_color_1
-    } else if cell_noise_value < 0.7 {
-      cell_color_2
-    } else if cell_noise_value < 0.75 {
-      cell_color_3
-    } else {
-      cell_color_4
-    };
+    let cell_noise_value = sample_sphere(noise, &fragment.vertex_position, zoom, &offset).abs();
+  
+    // Use the noise value to assign a different shade of green to each cell
+    // and make the darker cells a bit more vivid
+    let final_color = palette.sample(cell_noise_value).saturate(1.3 - cell_noise_value * 0.6);
   
     // Adjust intensity to simulate lighting effects (optional)
     final_color * fragment.intensity
 }
   
-fn lava_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    // Base colors for the lava effect
-    let bright_color = Color::new(255, 240, 0); // Bright orange (lava-like)
-    let dark_color = Color::new(130, 20, 0);   // Darker red-orange
-  
-    // Get fragment position
-    let position = Vec3::new(
-      fragment.vertex_position.x,
-      fragment.vertex_position.y,
-      fragment.depth
-    );
+fn lava_shader(fragment: &Fragment, uniforms: &Uniforms, noise: &FastNoiseLite, palette: &Gradient) -> Color {
+    // Object space position, so the pattern stays on the surface when the camera moves
+    let position = fragment.vertex_position;
   
     // Base frequency and amplitude for the pulsating effect
     let base_frequency = 0.2;
     let pulsate_amplitude = 0.5;
-    let t = uniforms.time as f32 * 0.01;
+    let t = uniforms.time * 0.6;
   
     // Pulsate on the z-axis to change spot size
     let pulsate = (t * base_frequency).sin() * pulsate_amplitude;
   
     // Apply noise to coordinates with subtle pulsating on z-axis
     let zoom = 1000.0; // Constant zoom factor
-    let noise_value1 = uniforms.noise.get_noise_3d(
-      position.x * zoom,
-      position.y * zoom,
-      (position.z + pulsate) * zoom
-    );
-    let noise_value2 = uniforms.noise.get_noise_3d(
-      (position.x + 1000.0) * zoom,
-      (position.y + 1000.0) * zoom,
-      (position.z + 1000.0 + pulsate) * zoom
-    );
+    let noise_value1 = sample_3d(noise, &position, zoom, &(Vec3::new(0.0, 0.0, pulsate) * zoom));
+    let noise_value2 = sample_3d(noise, &position, zoom, &(Vec3::new(1000.0, 1000.0, 1000.0 + pulsate) * zoom));
     let noise_value = (noise_value1 + noise_value2) * 0.5;  // Averaging noise for smoother transitions
   
-    // Use lerp for color blending based on noise value
-    let color = dark_color.lerp(&bright_color, noise_value);
+    // Dark red-orange crust to bright lava, glowing hotter and yellower as it swells
+    let color = palette.sample(noise_value)
+        .shift_hue(pulsate * 16.0)
+        .brighten(1.0 + pulsate * 0.4);
   
     color * fragment.intensity
-}
\ No newline at end of file
+}
+
+// Takes a tangent space normal (z pointing out of the surface) to world space
+fn perturb_normal(fragment: &Fragment, tangent_normal: &Vec3) -> Vec3 {
+    let normal = fragment.normal;
+    // Re-orthogonalize, interpolation bends the frame away from the normal
+    let tangent = (fragment.tangent - normal * dot(&normal, &fragment.tangent)).normalize();
+    let bitangent = if dot(&normal.cross(&tangent), &fragment.bitangent) < 0.0 {
+        -normal.cross(&tangent)
+    } else {
+        normal.cross(&tangent)
+    };
+
+    (tangent * tangent_normal.x + bitangent * tangent_normal.y + normal * tangent_normal.z).normalize()
+}
+
+// Normal from a tangent space normal map texture, colors encode the -1..1 range
+fn sample_normal_map(fragment: &Fragment, normal_map: &Texture) -> Vec3 {
+    let encoded = normal_map.sample(fragment.tex_coords.x, fragment.tex_coords.y).to_vec3();
+    let tangent_normal = encoded * 2.0 - Vec3::new(1.0, 1.0, 1.0);
+    perturb_normal(fragment, &tangent_normal)
+}
+
+// Procedural bump: the noise is a height field over the object surface and its slope
+// along the tangent and bitangent tilts the normal
+fn bump_normal(fragment: &Fragment, uniforms: &Uniforms, noise: &FastNoiseLite, zoom: f32, strength: f32) -> Vec3 {
+    let to_object = mat4_to_mat3(&uniforms.model_matrix).try_inverse().unwrap_or(Mat3::identity());
+    let tangent = (to_object * fragment.tangent).normalize();
+    let bitangent = (to_object * fragment.bitangent).normalize();
+
+    let epsilon = 0.01;
+    let height = |p: Vec3| sample_3d(noise, &p, zoom, &Vec3::zeros());
+    let p = fragment.vertex_position;
+    let h = height(p);
+    let dh_dt = (height(p + tangent * epsilon) - h) / epsilon;
+    let dh_db = (height(p + bitangent * epsilon) - h) / epsilon;
+
+    let tangent_normal = Vec3::new(-dh_dt * strength, -dh_db * strength, 1.0).normalize();
+    perturb_normal(fragment, &tangent_normal)
+}
+
+// Diffuse light for a shading normal other than the interpolated one, keeping the shadow term
+fn relight(fragment: &Fragment, uniforms: &Uniforms, normal: &Vec3) -> f32 {
+    let light_dir = uniforms.light.direction_to(&fragment.world_position);
+    dot(normal, &light_dir).max(0.0) * uniforms.light.intensity * fragment.shadow
+}
+
+fn crater_shader(fragment: &Fragment, uniforms: &Uniforms, bump_noise: &FastNoiseLite, dust_noise: &FastNoiseLite, palette: &Gradient) -> Color {
+    let normal = match uniforms.normal_map {
+        Some(normal_map) => sample_normal_map(fragment, normal_map),
+        None => bump_normal(fragment, uniforms, bump_noise, 1000.0, 0.01),
+    };
+
+    let dust = sample_3d(dust_noise, &fragment.vertex_position, 300.0, &Vec3::zeros()) * 0.5 + 0.5;
+
+    palette.sample(dust) * relight(fragment, uniforms, &normal)
+}
+
+fn gas_giant_shader(fragment: &Fragment, uniforms: &Uniforms, noise: &FastNoiseLite, palette: &Gradient) -> Color {
+    let storm_color = Color::new(196, 86, 52);
+
+    let zoom = 100.0;
+    let t = uniforms.time * 12.0;
+    let p = fragment.vertex_position.normalize() * zoom + Vec3::new(t, 0.0, 0.0);
+    let fractal = Fractal::new(4, 2.0, 0.5);
+
+    // Bands follow the latitude, the warp bends them into swirls
+    let warped = domain_warp(noise, &p, 12.0, &fractal);
+    let band = (warped.y / zoom * 3.5).rem_euclid(1.0);
+    let storms = turbulence(noise, &warped, &fractal);
+
+    let color = palette.sample(band).lerp_perceptual(&storm_color, (storms - 0.35) * 2.0);
+
+    color * fragment.intensity
+}
+
+fn textured_shader(fragment: &Fragment, texture: Option<&(Texture, TextureMapping)>, palette: &Gradient) -> Color {
+    let color = match texture {
+        Some((texture, mapping)) => {
+            let uv = mapping.coords(fragment);
+            texture.sample(uv.x, uv.y)
+        }
+        None => palette.sample(0.0),
+    };
+
+    color * fragment.intensity
+}
@@ -0,0 +1,159 @@
+
+use nalgebra_glm::{Vec3, Vec4, Mat4, dot, look_at, ortho, perspective};
+use std::f32::consts::PI;
+use crate::framebuffer::Framebuffer;
+use crate::light::{Light, LightKind};
+
+// Looking directions and up vectors for the six faces of a point light cube map
+const CUBE_FACES: [([f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+    ([0.0, 0.0, 1.0], [0.0, -1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [0.0, -1.0, 0.0]),
+];
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowSettings {
+    pub resolution: usize,
+    pub bias: f32,
+    pub slope_bias: f32,
+    pub pcf_radius: i32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings {
+            resolution: 1024,
+            bias: 0.01,
+            slope_bias: 0.03,
+            pcf_radius: 1,
+        }
+    }
+}
+
+pub struct ShadowFace {
+    pub depth: Framebuffer,
+    pub view_matrix: Mat4,
+    pub projection_matrix: Mat4,
+}
+
+pub struct ShadowMap {
+    pub faces: Vec<ShadowFace>,
+    pub settings: ShadowSettings,
+    light: Light,
+}
+
+impl ShadowMap {
+    pub fn new(light: &Light, settings: ShadowSettings, scene_center: Vec3, scene_radius: f32) -> Self {
+        let resolution = settings.resolution;
+        let matrices = match light.kind {
+            LightKind::Directional { direction } => {
+                let eye = scene_center - direction * scene_radius * 2.0;
+                let up = if direction.y.abs() > 0.99 {
+                    Vec3::new(0.0, 0.0, 1.0)
+                } else {
+                    Vec3::new(0.0, 1.0, 0.0)
+                };
+                let r = scene_radius;
+                vec![(look_at(&eye, &scene_center, &up), ortho(-r, r, -r, r, 0.1, r * 4.0))]
+            }
+            LightKind::Point { position } => {
+                let far = (position - scene_center).magnitude() + scene_radius * 2.0;
+                let projection = perspective(1.0, PI / 2.0, 0.05, far);
+                CUBE_FACES.iter().map(|(dir, up)| {
+                    let target = position + Vec3::new(dir[0], dir[1], dir[2]);
+                    let up = Vec3::new(up[0], up[1], up[2]);
+                    (look_at(&position, &target, &up), projection)
+                }).collect()
+            }
+        };
+
+        let faces = matrices.into_iter().map(|(view_matrix, projection_matrix)| ShadowFace {
+            depth: Framebuffer::new_depth_only(resolution, resolution),
+            view_matrix,
+            projection_matrix,
+        }).collect();
+
+        ShadowMap { faces, settings, light: *light }
+    }
+
+    // Fraction of the PCF kernel that sees the light, 0.0 is fully shadowed
+    pub fn visibility(&self, world_position: &Vec3, normal: &Vec3) -> f32 {
+        let face = self.face_for(world_position);
+        let clip = face.projection_matrix * face.view_matrix
+            * Vec4::new(world_position.x, world_position.y, world_position.z, 1.0);
+
+        if clip.w <= 0.0 {
+            return 1.0;
+        }
+
+        let ndc_x = clip.x / clip.w;
+        let ndc_y = clip.y / clip.w;
+        if !(-1.0..=1.0).contains(&ndc_x) || !(-1.0..=1.0).contains(&ndc_y) {
+            return 1.0;
+        }
+
+        let resolution = self.settings.resolution as f32;
+        let center_x = ((ndc_x + 1.0) * 0.5 * resolution) as i32;
+        let center_y = ((1.0 - ndc_y) * 0.5 * resolution) as i32;
+
+        let to_light = self.light.direction_to(world_position);
+        let n_dot_l = dot(&normal.normalize(), &to_light).clamp(0.0, 1.0);
+        let bias = self.settings.bias + self.settings.slope_bias * (1.0 - n_dot_l);
+        let depth = light_depth(&self.light, world_position) - bias;
+
+        let radius = self.settings.pcf_radius;
+        let mut lit = 0;
+        let mut samples = 0;
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let x = center_x + dx;
+                let y = center_y + dy;
+                if x < 0 || y < 0 || x as usize >= face.depth.width || y as usize >= face.depth.height {
+                    continue;
+                }
+
+                samples += 1;
+                if depth <= face.depth.depth_at(x as usize, y as usize) {
+                    lit += 1;
+                }
+            }
+        }
+
+        if samples == 0 {
+            1.0
+        } else {
+            lit as f32 / samples as f32
+        }
+    }
+
+    fn face_for(&self, world_position: &Vec3) -> &ShadowFace {
+        match self.light.kind {
+            LightKind::Directional { .. } => &self.faces[0],
+            LightKind::Point { position } => {
+                let d = world_position - position;
+                let (ax, ay, az) = (d.x.abs(), d.y.abs(), d.z.abs());
+                let index = if ax >= ay && ax >= az {
+                    if d.x > 0.0 { 0 } else { 1 }
+                } else if ay >= az {
+                    if d.y > 0.0 { 2 } else { 3 }
+                } else if d.z > 0.0 {
+                    4
+                } else {
+                    5
+                };
+                &self.faces[index]
+            }
+        }
+    }
+}
+
+// Depth stored in the shadow map: distance travelled by the light to reach the point
+pub fn light_depth(light: &Light, world_position: &Vec3) -> f32 {
+    match light.kind {
+        LightKind::Directional { direction } => dot(world_position, &direction),
+        LightKind::Point { position } => (world_position - position).magnitude(),
+    }
+}
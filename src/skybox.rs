@@ -0,0 +1,163 @@
+
+use nalgebra_glm::{Vec2, Vec3, Vec4, Mat4};
+use fastnoise_lite::{FastNoiseLite, NoiseType};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use std::f32::consts::PI;
+use crate::framebuffer::Framebuffer;
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::texture::Texture;
+use crate::gradient::{Gradient, BlendSpace};
+use crate::noise::{NoiseConfig, Fractal, lat_long, lat_long_to_direction, fbm};
+
+pub enum Skybox {
+    // Keeps the framebuffer background color
+    Flat,
+    // Equirectangular image, u is the longitude and v the latitude like the baked textures
+    Equirectangular(Texture),
+    Starfield(Starfield),
+}
+
+struct Star {
+    direction: Vec3,
+    color: Color,
+    brightness: f32,
+    phase: f32,
+}
+
+// Milky Way band baked once into an equirectangular texture, with stars drawn on top as
+// points so they stay sharp and can twinkle
+pub struct Starfield {
+    milky_way: Texture,
+    stars: Vec<Star>,
+    pub twinkle: f32,
+}
+
+impl Starfield {
+    pub fn new(seed: u64, star_count: usize) -> Self {
+        // Tilted against the orbits, like the galactic plane seen from the solar system
+        let galactic_pole = Vec3::new(0.35, 1.0, 0.45).normalize();
+        let band = |direction: &Vec3| {
+            let height = direction.dot(&galactic_pole) / 0.22;
+            (-height * height).exp()
+        };
+
+        let noise = NoiseConfig::new(NoiseType::OpenSimplex2, seed as i32).build();
+        let glow = Gradient::from_hex_stops(&[(0.0, "#04040c"), (0.35, "#1c1836"), (0.7, "#5a4f78"), (1.0, "#d8cfe8")])
+            .expect("valid palette")
+            .in_space(BlendSpace::Perceptual);
+
+        let mut milky_way = Texture::new(512, 256);
+        for y in 0..milky_way.height {
+            for x in 0..milky_way.width {
+                let uv = Vec2::new((x as f32 + 0.5) / milky_way.width as f32, (y as f32 + 0.5) / milky_way.height as f32);
+                let direction = lat_long_to_direction(&uv);
+                milky_way.set_pixel(x, y, glow.sample(milky_way_density(&noise, &direction, band(&direction))));
+            }
+        }
+
+        // Hot blue-white, sun-like and cool orange stars
+        let temperature = Gradient::from_hex(&["#ffb07a", "#fff4e8", "#b4ccff"]).expect("valid palette");
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut stars = Vec::with_capacity(star_count);
+        while stars.len() < star_count {
+            let z: f32 = rng.gen_range(-1.0..1.0);
+            let angle: f32 = rng.gen_range(0.0..2.0 * PI);
+            let ring = (1.0 - z * z).sqrt();
+            let direction = Vec3::new(ring * angle.cos(), z, ring * angle.sin());
+
+            // Denser along the band
+            if rng.gen::<f32>() > 0.3 + 0.7 * band(&direction) {
+                continue;
+            }
+            stars.push(Star {
+                direction,
+                color: temperature.sample(rng.gen()),
+                brightness: 0.2 + 0.8 * rng.gen::<f32>().powf(3.0),
+                phase: rng.gen_range(0.0..2.0 * PI),
+            });
+        }
+
+        // Twinkling animates the sky, which keeps a static scene from idling, so it starts off
+        Starfield { milky_way, stars, twinkle: 0.0 }
+    }
+
+    fn render_stars(&self, framebuffer: &mut Framebuffer, view_matrix: &Mat4, projection_matrix: &Mat4, viewport_matrix: &Mat4, time: f32) {
+        for star in &self.stars {
+            // w = 0, directions ignore the camera position and stars stay infinitely far
+            let view = view_matrix * Vec4::new(star.direction.x, star.direction.y, star.direction.z, 0.0);
+            if view.z >= 0.0 {
+                continue;
+            }
+            let clip = projection_matrix * Vec4::new(view.x, view.y, view.z, 1.0);
+            let screen = viewport_matrix * (clip / clip.w);
+            if screen.x < 0.0 || screen.y < 0.0 {
+                continue;
+            }
+
+            let flicker = 0.5 + 0.5 * (time * 9.0 + star.phase).sin();
+            let brightness = star.brightness * (1.0 - self.twinkle * flicker);
+            let (x, y) = (screen.x as usize, screen.y as usize);
+
+            add_light(framebuffer, x, y, star.color * brightness);
+            // Bright stars bleed into the pixels around them
+            if star.brightness > 0.7 {
+                let halo = star.color * (brightness * 0.35);
+                add_light(framebuffer, x + 1, y, halo);
+                add_light(framebuffer, x, y + 1, halo);
+                if x > 0 { add_light(framebuffer, x - 1, y, halo); }
+                if y > 0 { add_light(framebuffer, x, y - 1, halo); }
+            }
+        }
+    }
+}
+
+fn milky_way_density(noise: &FastNoiseLite, direction: &Vec3, band: f32) -> f32 {
+    let fractal = Fractal::new(5, 2.0, 0.5);
+    let clouds = fbm(noise, &(direction * 250.0), &fractal) * 0.5 + 0.5;
+    // Dark dust lanes cut through the brightest part of the band
+    let dust = fbm(noise, &(direction * 600.0 + Vec3::new(31.0, 7.0, 12.0)), &fractal).abs();
+
+    (band * clouds * (0.4 + 0.8 * dust) * 1.4).clamp(0.0, 1.0)
+}
+
+fn add_light(framebuffer: &mut Framebuffer, x: usize, y: usize, light: Color) {
+    if x < framebuffer.width && y < framebuffer.height {
+        let background = Color::from_hex(framebuffer.color_at(x, y));
+        framebuffer.background_point(x, y, (background + light).to_hex());
+    }
+}
+
+impl Skybox {
+    pub fn is_animated(&self) -> bool {
+        matches!(self, Skybox::Starfield(starfield) if starfield.twinkle > 0.0)
+    }
+
+    // Background pass, fills every pixel not covered by geometry. Each pixel is turned into a
+    // view space ray with the inverse projection, then into a world direction by the camera.
+    pub fn render(&self, framebuffer: &mut Framebuffer, camera: &Camera, view_matrix: &Mat4, projection_matrix: &Mat4, viewport_matrix: &Mat4, time: f32) {
+        let texture = match self {
+            Skybox::Flat => return,
+            Skybox::Equirectangular(texture) => texture,
+            Skybox::Starfield(starfield) => &starfield.milky_way,
+        };
+
+        let inverse_projection = projection_matrix.try_inverse().unwrap_or(Mat4::identity());
+        for y in 0..framebuffer.height {
+            for x in 0..framebuffer.width {
+                let ndc_x = (x as f32 + 0.5) / framebuffer.width as f32 * 2.0 - 1.0;
+                let ndc_y = 1.0 - (y as f32 + 0.5) / framebuffer.height as f32 * 2.0;
+                let far = inverse_projection * Vec4::new(ndc_x, ndc_y, 1.0, 1.0);
+
+                let direction = camera.basis_change(&(Vec3::new(far.x, far.y, far.z) / far.w));
+                let uv = lat_long(&direction);
+                framebuffer.background_point(x, y, texture.sample(uv.x, uv.y).to_hex());
+            }
+        }
+
+        if let Skybox::Starfield(starfield) = self {
+            starfield.render_stars(framebuffer, view_matrix, projection_matrix, viewport_matrix, time);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+
+use std::fs;
+use std::io::{self, Write};
+use crate::color::Color;
+
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Texture {
+    pub fn new(width: usize, height: usize) -> Self {
+        Texture { width, height, pixels: vec![Color::black(); width * height] }
+    }
+
+    // Loads a binary PPM (P6) image with 8 bits per channel
+    pub fn load_ppm(filename: &str) -> io::Result<Self> {
+        let bytes = fs::read(filename)?;
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", filename, message));
+
+        // Header is "P6 <width> <height> <maxval>" separated by whitespace, comments start with '#'
+        let mut fields = Vec::new();
+        let mut cursor = 0;
+        while fields.len() < 4 {
+            while cursor < bytes.len() && (bytes[cursor].is_ascii_whitespace() || bytes[cursor] == b'#') {
+                if bytes[cursor] == b'#' {
+                    while cursor < bytes.len() && bytes[cursor] != b'\n' {
+                        cursor += 1;
+                    }
+                }
+                cursor += 1;
+            }
+            let start = cursor;
+            while cursor < bytes.len() && !bytes[cursor].is_ascii_whitespace() {
+                cursor += 1;
+            }
+            if start == cursor {
+                return Err(invalid("truncated header"));
+            }
+            fields.push(String::from_utf8_lossy(&bytes[start..cursor]).to_string());
+        }
+        // Exactly one whitespace byte separates the header from the pixel data
+        cursor += 1;
+
+        if fields[0] != "P6" {
+            return Err(invalid("only binary P6 images are supported"));
+        }
+        let width: usize = fields[1].parse().map_err(|_| invalid("bad width"))?;
+        let height: usize = fields[2].parse().map_err(|_| invalid("bad height"))?;
+        if width == 0 || height == 0 {
+            return Err(invalid("empty image"));
+        }
+        if fields[3] != "255" {
+            return Err(invalid("only 8 bit images are supported"));
+        }
+
+        let size = width.checked_mul(height).and_then(|n| n.checked_mul(3)).ok_or_else(|| invalid("image too large"))?;
+        let data = bytes.get(cursor..cursor.saturating_add(size)).ok_or_else(|| invalid("truncated pixel data"))?;
+        let pixels = data.chunks(3).map(|rgb| Color::new(rgb[0], rgb[1], rgb[2])).collect();
+
+        Ok(Texture { width, height, pixels })
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    // Writes a binary PPM (P6) image that load_ppm can read back
+    pub fn save_ppm(&self, filename: &str) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(filename)?);
+        write!(file, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for pixel in &self.pixels {
+            let hex = pixel.to_hex();
+            file.write_all(&[(hex >> 16) as u8, (hex >> 8) as u8, hex as u8])?;
+        }
+        file.flush()
+    }
+
+    // Bilinear sample with repeat wrapping, (0, 0) is the top left corner
+    pub fn sample(&self, u: f32, v: f32) -> Color {
+        let x = u.rem_euclid(1.0) * self.width as f32 - 0.5;
+        let y = v.rem_euclid(1.0) * self.height as f32 - 0.5;
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+
+        let wrap_x = |x: f32| (x as i64).rem_euclid(self.width as i64) as usize;
+        let wrap_y = |y: f32| (y as i64).rem_euclid(self.height as i64) as usize;
+
+        let top = self.get_pixel(wrap_x(x0), wrap_y(y0)).lerp(&self.get_pixel(wrap_x(x0 + 1.0), wrap_y(y0)), tx);
+        let bottom = self.get_pixel(wrap_x(x0), wrap_y(y0 + 1.0)).lerp(&self.get_pixel(wrap_x(x0 + 1.0), wrap_y(y0 + 1.0)), tx);
+
+        top.lerp(&bottom, ty)
+    }
+}
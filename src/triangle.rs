@@ -2,15 +2,14 @@ use nalgebra_glm::{Vec3, dot};
 use crate::fragment::Fragment;
 use crate::vertex::Vertex;
 use crate::color::Color;
+use crate::light::Light;
 
-pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
+pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex, light: &Light) -> Vec<Fragment> {
   let mut fragments = Vec::new();
   let (a, b, c) = (v1.transformed_position, v2.transformed_position, v3.transformed_position);
 
   let (min_x, min_y, max_x, max_y) = calculate_bounding_box(&a, &b, &c);
 
-  let light_dir = Vec3::new(0.0, 0.0, 1.0);
-
   let triangle_area = edge_function(&a, &b, &c);
 
   for y in min_y..=max_y {
@@ -19,14 +18,18 @@ pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
 
       let (w1, w2, w3) = barycentric_coordinates(&point, &a, &b, &c, triangle_area);
 
-      if w1 >= 0.0 && w1 <= 1.0 && 
-         w2 >= 0.0 && w2 <= 1.0 &&
-         w3 >= 0.0 && w3 <= 1.0 {
+      if (0.0..=1.0).contains(&w1) &&
+         (0.0..=1.0).contains(&w2) &&
+         (0.0..=1.0).contains(&w3) {
 
-        let normal = v1.transformed_normal * w1 + v2.transformed_normal * w2 + v3.transformed_normal * w3;
+        // Lighting is done in world space, where both the normals and the light live
+        let normal = v1.world_normal * w1 + v2.world_normal * w2 + v3.world_normal * w3;
         let normal = normal.normalize();
 
-        let intensity = dot(&normal, &light_dir).max(0.0);
+        let world_position = v1.world_position * w1 + v2.world_position * w2 + v3.world_position * w3;
+        let light_dir = light.direction_to(&world_position);
+
+        let intensity = dot(&normal, &light_dir).max(0.0) * light.intensity;
 
         let base_color = Color::new(100, 100, 100);
         let lit_color = base_color * intensity;
@@ -34,17 +37,30 @@ pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
         let depth = a.z * w1 + b.z * w2 + c.z * w3;
 
         let vertex_position = v1.position * w1 + v2.position * w2 + v3.position * w3;
+        let view_position = v1.view_position * w1 + v2.view_position * w2 + v3.view_position * w3;
+        let view_normal = (v1.view_normal * w1 + v2.view_normal * w2 + v3.view_normal * w3).normalize();
+        let tex_coords = v1.tex_coords * w1 + v2.tex_coords * w2 + v3.tex_coords * w3;
+        let tangent = (v1.world_tangent * w1 + v2.world_tangent * w2 + v3.world_tangent * w3).normalize();
+        let bitangent = (v1.world_bitangent * w1 + v2.world_bitangent * w2 + v3.world_bitangent * w3).normalize();
 
         fragments.push(
-            Fragment::new(
-                x as f32,
-                y as f32,
-                lit_color,
-                depth,
-                normal,
-                intensity,
-                vertex_position,
-            )
+            Fragment {
+                world_position,
+                view_position,
+                view_normal,
+                tex_coords,
+                tangent,
+                bitangent,
+                ..Fragment::new(
+                    x as f32,
+                    y as f32,
+                    lit_color,
+                    depth,
+                    normal,
+                    intensity,
+                    vertex_position,
+                )
+            }
         );
       }
     }
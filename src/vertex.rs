@@ -6,9 +6,16 @@ pub struct Vertex {
   pub position: Vec3,
   pub normal: Vec3,
   pub tex_coords: Vec2,
+  pub tangent: Vec3,
+  pub bitangent: Vec3,
   pub color: Color,
   pub transformed_position: Vec3,
-  pub transformed_normal: Vec3,
+  pub world_position: Vec3,
+  pub world_normal: Vec3,
+  pub view_position: Vec3,
+  pub view_normal: Vec3,
+  pub world_tangent: Vec3,
+  pub world_bitangent: Vec3,
 }
 
 impl Vertex {
@@ -17,9 +24,16 @@ impl Vertex {
       position,
       normal,
       tex_coords,
+      tangent: Vec3::new(1.0, 0.0, 0.0),
+      bitangent: Vec3::new(0.0, 0.0, 1.0),
       color: Color::black(),
       transformed_position: position,
-      transformed_normal: normal,
+      world_position: position,
+      world_normal: normal,
+      view_position: position,
+      view_normal: normal,
+      world_tangent: Vec3::new(1.0, 0.0, 0.0),
+      world_bitangent: Vec3::new(0.0, 0.0, 1.0),
     }
   }
 
@@ -28,15 +42,22 @@ impl Vertex {
       position,
       normal: Vec3::new(0.0, 0.0, 0.0),
       tex_coords: Vec2::new(0.0, 0.0),
+      tangent: Vec3::new(0.0, 0.0, 0.0),
+      bitangent: Vec3::new(0.0, 0.0, 0.0),
       color,
       transformed_position: Vec3::new(0.0, 0.0, 0.0),
-      transformed_normal: Vec3::new(0.0, 0.0, 0.0),
+      world_position: Vec3::new(0.0, 0.0, 0.0),
+      world_normal: Vec3::new(0.0, 0.0, 0.0),
+      view_position: Vec3::new(0.0, 0.0, 0.0),
+      view_normal: Vec3::new(0.0, 0.0, 0.0),
+      world_tangent: Vec3::new(0.0, 0.0, 0.0),
+      world_bitangent: Vec3::new(0.0, 0.0, 0.0),
     }
   }
 
   pub fn set_transformed(&mut self, position: Vec3, normal: Vec3) {
     self.transformed_position = position;
-    self.transformed_normal = normal;
+    self.world_normal = normal;
   }
 }
 
@@ -46,9 +67,16 @@ impl Default for Vertex {
       position: Vec3::new(0.0, 0.0, 0.0),
       normal: Vec3::new(0.0, 1.0, 0.0),
       tex_coords: Vec2::new(0.0, 0.0),
+      tangent: Vec3::new(1.0, 0.0, 0.0),
+      bitangent: Vec3::new(0.0, 0.0, 1.0),
       color: Color::black(),
       transformed_position: Vec3::new(0.0, 0.0, 0.0),
-      transformed_normal: Vec3::new(0.0, 1.0, 0.0),
+      world_position: Vec3::new(0.0, 0.0, 0.0),
+      world_normal: Vec3::new(0.0, 1.0, 0.0),
+      view_position: Vec3::new(0.0, 0.0, 0.0),
+      view_normal: Vec3::new(0.0, 1.0, 0.0),
+      world_tangent: Vec3::new(1.0, 0.0, 0.0),
+      world_bitangent: Vec3::new(0.0, 0.0, 1.0),
     }
   }
 }